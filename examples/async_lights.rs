@@ -0,0 +1,31 @@
+extern crate hueclient;
+use hueclient::async_bridge::BridgeAsync;
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("usage : {:?} <username>", args[0]);
+        return;
+    }
+    let bridge = BridgeAsync::discover_required()
+        .await
+        .with_user(args[1].to_string());
+    match bridge.get_all_lights().await {
+        Ok(lights) => {
+            for l in lights.iter() {
+                println!(
+                    "{:2} {:20} {}",
+                    l.id,
+                    l.light.name,
+                    if l.light.state.on { "on" } else { "off" }
+                );
+            }
+        }
+        Err(err) => {
+            println!("Error: {}", err);
+            ::std::process::exit(2)
+        }
+    }
+}
@@ -0,0 +1,24 @@
+extern crate hueclient;
+use hueclient::bridge::{Bridge, CommandLight};
+use std::env;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("usage : {:?} <username>", args[0]);
+        return;
+    }
+    let bridge = Bridge::discover_required().with_user(args[1].to_string());
+    let snapshot = bridge.get_all_lights().unwrap();
+
+    let red = CommandLight::default().on().with_rgb(255, 0, 0);
+    for l in snapshot.iter() {
+        bridge.set_light_state(l.id, &red).unwrap();
+    }
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    for l in snapshot.iter() {
+        let restore: CommandLight = l.light.state.into();
+        println!("{}: {:?}", l, bridge.set_light_state(l.id, &restore));
+    }
+}
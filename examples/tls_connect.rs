@@ -0,0 +1,33 @@
+extern crate hueclient;
+use hueclient::bridge::Bridge;
+use std::env;
+
+// Save the bridge certificate once with
+//   openssl s_client -showcerts -connect <ip>:443 </dev/null | openssl x509 > bridge.pem
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        println!("usage : {:?} <ip> <username> <cert.pem>", args[0]);
+        return;
+    }
+    let ip = args[1].parse().expect("could not parse bridge ip");
+    let pem = std::fs::read(&args[3]).expect("could not read certificate");
+    let bridge = match Bridge::for_ip(ip).with_tls(&pem) {
+        Ok(bridge) => bridge.with_user(args[2].to_string()),
+        Err(err) => {
+            println!("Error: {}", err);
+            ::std::process::exit(2)
+        }
+    };
+    match bridge.get_all_lights() {
+        Ok(lights) => {
+            for l in lights.iter() {
+                println!("{:2} {:20} {}", l.id, l.light.name, l.light.state.on);
+            }
+        }
+        Err(err) => {
+            println!("Error: {}", err);
+            ::std::process::exit(2)
+        }
+    }
+}
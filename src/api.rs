@@ -0,0 +1,154 @@
+//! The bridge endpoints as a trait, so that application code can be generic
+//! over the real [`Bridge`] and a stand-in such as
+//! [`MockBridge`](crate::testing::MockBridge) (`testing` feature).
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::bridge::*;
+use crate::*;
+
+macro_rules! bridge_api {
+    ($(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        pub trait HueBridgeApi {
+            $(fn $name(&self $(, $arg: $ty)*) -> Result<$ret, HueError>;)*
+        }
+
+        impl HueBridgeApi for Bridge {
+            $(fn $name(&self $(, $arg: $ty)*) -> Result<$ret, HueError> {
+                Bridge::$name(self $(, $arg)*)
+            })*
+        }
+
+        #[cfg(feature = "testing")]
+        impl HueBridgeApi for crate::testing::MockBridge {
+            $(fn $name(&self $(, $arg: $ty)*) -> Result<$ret, HueError> {
+                let args: Vec<String> = vec![$(format!("{:?}", $arg)),*];
+                self.call(stringify!($name), args)
+            })*
+        }
+    };
+}
+
+bridge_api! {
+    fn ping(&self) -> ();
+    fn get_all_lights(&self) -> Vec<IdentifiedLight>;
+    fn get_all_lights_with_timeout(&self, timeout: Duration) -> PartialLightResult;
+    fn get_lights_by_model(&self, modelid: &str) -> Vec<IdentifiedLight>;
+    fn get_reachable_lights(&self) -> Vec<IdentifiedLight>;
+    fn get_lights_of_type(&self, light_type: &str) -> Vec<IdentifiedLight>;
+    fn find_light_by_name(&self, name: &str) -> Option<IdentifiedLight>;
+    fn find_lights_by_name_prefix(&self, prefix: &str) -> Vec<IdentifiedLight>;
+    fn get_all_lights_raw(&self) -> Vec<u8>;
+    fn get_all_lights_sorted_by_brightness(&self) -> Vec<IdentifiedLight>;
+    fn get_light_reachability(&self) -> HashMap<usize, bool>;
+    fn get_light(&self, id: usize) -> Light;
+    fn get_light_new(&self) -> Vec<IdentifiedLight>;
+    fn search_for_new_lights(&self, device_ids: Option<&[&str]>) -> ();
+    fn get_new_lights(&self) -> NewLightsResponse;
+    fn set_light_name(&self, id: usize, name: &str) -> ();
+    fn rename_light(&self, id: usize, name: &str) -> ();
+    fn delete_light(&self, id: usize) -> ();
+    fn set_light_state(&self, light: usize, command: &CommandLight) -> Value;
+    fn batch_set_light_states(
+        &self,
+        commands: &[(usize, CommandLight)]
+    ) -> Vec<(usize, Result<Value, HueError>)>;
+    fn set_light_state_unchecked(&self, light: usize, command: &CommandLight) -> Value;
+    fn flash_light(&self, id: usize) -> ();
+    fn flash_light_long(&self, id: usize) -> ();
+    fn start_color_loop(&self, id: usize) -> ();
+    fn stop_color_loop(&self, id: usize) -> ();
+    fn flash_group(&self, id: usize) -> ();
+    fn get_all_groups(&self) -> Vec<IdentifiedGroup>;
+    fn get_group(&self, id: usize) -> Group;
+    fn create_group(&self, name: &str, lights: &[usize]) -> usize;
+    fn create_room(&self, name: &str, lights: &[usize], room_class: RoomClass) -> usize;
+    fn create_zone(&self, name: &str, lights: &[usize]) -> usize;
+    fn set_group_state(&self, id: usize, command: &CommandGroup) -> Value;
+    fn set_all_lights(&self, cmd: CommandLight) -> ();
+    fn dim_all_lights(&self, bri: u8) -> ();
+    fn set_group_attributes(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        lights: Option<&[usize]>
+    ) -> Value;
+    fn update_group(&self, id: usize, name: Option<&str>, lights: Option<&[usize]>) -> ();
+    fn set_group_name(&self, id: usize, name: &str) -> ();
+    fn delete_group(&self, id: usize) -> ();
+    fn set_multiple_lights(&self, ids: &[usize], command: CommandLight) -> ();
+    fn get_all_scenes(&self) -> BTreeMap<String, Scene>;
+    fn get_scenes_for_group(&self, group_id: usize) -> Vec<SceneSummary>;
+    fn get_scene(&self, id: &str) -> SceneDetail;
+    fn create_scene(&self, name: &str, lights: &[usize]) -> String;
+    fn set_scene_light_state(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+        command: &CommandLight
+    ) -> Value;
+    fn update_scene_light_state(&self, scene_id: &str, light_id: usize, cmd: CommandLight) -> ();
+    fn recall_scene(&self, group_id: usize, scene_id: &str) -> Value;
+    fn activate_scene(&self, group_id: usize, scene_id: &str) -> ();
+    fn store_current_scene(&self, scene_id: &str) -> ();
+    fn append_light_to_scene(&self, scene_id: &str, light_id: usize) -> ();
+    fn delete_scene(&self, id: &str) -> ();
+    fn get_all_schedules(&self) -> Vec<IdentifiedSchedule>;
+    fn get_schedule(&self, id: usize) -> Schedule;
+    fn create_schedule(&self, schedule: &Schedule) -> usize;
+    fn update_schedule(&self, id: usize, schedule: &Schedule) -> Value;
+    fn delete_schedule(&self, id: usize) -> ();
+    fn get_all_sensors(&self) -> Vec<IdentifiedSensor>;
+    fn get_sensor(&self, id: usize) -> Sensor;
+    fn create_sensor(&self, sensor: &NewSensor) -> usize;
+    fn update_sensor_name(&self, id: usize, name: &str) -> Value;
+    fn set_sensor_config(&self, id: usize, config: &SensorConfig) -> Value;
+    fn set_sensor_state(&self, id: usize, state: &BTreeMap<String, Value>) -> Value;
+    fn update_sensor_state(&self, id: usize, state: &SensorStatePatch) -> ();
+    fn delete_sensor(&self, id: usize) -> ();
+    fn get_all_rules(&self) -> Vec<IdentifiedRule>;
+    fn get_enabled_rules(&self) -> Vec<IdentifiedRule>;
+    fn get_disabled_rules(&self) -> Vec<IdentifiedRule>;
+    fn get_rule(&self, id: usize) -> Rule;
+    fn create_rule(
+        &self,
+        name: &str,
+        conditions: &[RuleCondition],
+        actions: &[RuleAction]
+    ) -> usize;
+    fn update_rule(&self, id: usize, rule: &Rule) -> Value;
+    fn update_rule_status(&self, id: usize, enabled: bool) -> ();
+    fn delete_rule(&self, id: usize) -> ();
+    fn get_all_resourcelinks(&self) -> Vec<IdentifiedResourceLink>;
+    fn get_resourcelink(&self, id: usize) -> ResourceLink;
+    fn create_resourcelink(&self, name: &str, description: &str, links: &[&str]) -> usize;
+    fn update_resourcelink(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        description: Option<&str>,
+        links: Option<&[&str]>
+    ) -> Value;
+    fn delete_resourcelink(&self, id: usize) -> ();
+    fn get_capabilities(&self) -> Capabilities;
+    fn get_config(&self) -> BridgeConfig;
+    fn get_timezone_list(&self) -> Vec<String>;
+    fn get_firmware_update_status(&self) -> UpdateStatus;
+    fn install_firmware_update(&self) -> ();
+    fn check_for_firmware_update(&self) -> ();
+    fn get_full_state(&self) -> FullState;
+    fn set_config(&self, patch: &BridgeConfigPatch) -> Value;
+    fn backup(&self) -> Vec<u8>;
+    fn restore(&self, data: &[u8]) -> ();
+    fn factory_reset(&self) -> ();
+    fn get_api_version(&self) -> ApiVersion;
+    fn requires_api_version(&self, min: ApiVersion) -> ();
+    fn get_zigbee_channel(&self) -> u8;
+    fn set_zigbee_channel(&self, channel: u8) -> ();
+    fn touchlink(&self) -> ();
+    fn get_whitelist(&self) -> BTreeMap<String, WhitelistEntry>;
+    fn delete_user(&self, username_to_delete: &str) -> ();
+}
@@ -0,0 +1,1017 @@
+//! Non-blocking flavour of [`Bridge`](crate::bridge::Bridge), for use from
+//! within a Tokio runtime. Enabled by the `async` feature.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::bridge::*;
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct BridgeAsync {
+    pub ip: IpAddr,
+    pub username: Option<String>,
+    client: reqwest::Client,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    #[cfg(feature = "tls")]
+    tls: Option<native_tls::TlsConnector>,
+    port: Option<u16>,
+    scheme: &'static str,
+    max_concurrency: usize,
+}
+
+impl BridgeAsync {
+    pub fn for_ip(ip: IpAddr) -> BridgeAsync {
+        BridgeAsync {
+            ip,
+            username: None,
+            client: reqwest::Client::new(),
+            timeout: None,
+            connect_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            port: None,
+            scheme: "http",
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        }
+    }
+
+    /// Discovers as `Bridge::discover` does: a (blocking) SSDP probe of up
+    /// to five seconds first, then the N-UPnP lookup when no bridge answers.
+    pub async fn discover() -> Option<BridgeAsync> {
+        discovery::discover_bridge_ips_async()
+            .await
+            .into_iter()
+            .next()
+            .map(BridgeAsync::for_ip)
+    }
+
+    pub async fn discover_required() -> BridgeAsync {
+        BridgeAsync::discover()
+            .await
+            .unwrap_or_else(|| panic!("No bridge found!"))
+    }
+
+    pub fn with_user(self, username: String) -> BridgeAsync {
+        BridgeAsync {
+            username: Some(username),
+            ..self
+        }
+    }
+
+    /// Unlike the blocking client, the async one has no default timeout.
+    pub fn with_timeout(self, d: Duration) -> BridgeAsync {
+        BridgeAsync {
+            timeout: Some(d),
+            ..self
+        }
+    }
+
+    /// Bounds the requests `batch_set_light_states` and
+    /// `get_all_lights_with_timeout` keep in flight, 4 by default, see
+    /// `Bridge::with_max_concurrency`.
+    pub fn with_max_concurrency(self, n: usize) -> BridgeAsync {
+        BridgeAsync {
+            max_concurrency: n.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_port(self, port: u16) -> BridgeAsync {
+        BridgeAsync {
+            port: Some(port),
+            ..self
+        }
+    }
+
+    fn host(&self) -> String {
+        host(self.ip, self.port)
+    }
+
+    pub fn with_connect_timeout(self, d: Duration) -> BridgeAsync {
+        let bridge = BridgeAsync {
+            connect_timeout: Some(d),
+            ..self
+        };
+        BridgeAsync {
+            client: bridge.build_client().expect("Client::builder()"),
+            ..bridge
+        }
+    }
+
+    /// See `Bridge::with_tls`.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(self, cert_pem: &[u8]) -> Result<BridgeAsync, HueError> {
+        let connector = pinned_tls(cert_pem)?;
+        let bridge = BridgeAsync {
+            tls: Some(connector),
+            scheme: "https",
+            ..self
+        };
+        Ok(BridgeAsync {
+            client: bridge.build_client()?,
+            ..bridge
+        })
+    }
+
+    fn build_client(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(d) = self.connect_timeout {
+            builder = builder.connect_timeout(d);
+        }
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = &self.tls {
+                builder = builder.use_preconfigured_tls(tls.clone());
+            }
+        }
+        builder.build()
+    }
+
+    /// Checks that the bridge answers on `/api`, which needs no username.
+    pub async fn ping(&self) -> Result<(), HueError> {
+        let url = format!("{}://{}/api", self.scheme, self.host());
+        let status = self.send_raw(self.client.get(&url[..])).await?.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(HueError::ProtocolError {
+                msg: format!("unreachable: the bridge answered {}", status),
+            })
+        }
+    }
+
+    pub async fn register_user(&mut self, devicetype: &str) -> Result<String, HueError> {
+        let url = format!("{}://{}/api", self.scheme, self.host());
+        let resp = self
+            .send(self.client.post(&url[..]).json(&RegisterUser {
+                devicetype,
+                generateclientkey: false,
+            }))
+            .await?;
+        let success: RegisterUserSuccess = parse(resp)?;
+
+        self.username = Some(success.success.username.clone());
+
+        Ok(success.success.username)
+    }
+
+    pub async fn get_all_lights(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        let resp: HashMap<String, Light> = self.get("lights").await?;
+        identify(resp, "Light", |id, light| IdentifiedLight { id, light })
+    }
+
+    /// Fetches the lights one by one, `with_max_concurrency` at a time, and
+    /// reports those that did not arrive within `timeout` in `errors`, see
+    /// `Bridge::get_all_lights_with_timeout`.
+    pub async fn get_all_lights_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<PartialLightResult, HueError> {
+        let deadline = Instant::now() + timeout;
+        let ids = self
+            .clone()
+            .with_timeout(timeout)
+            .get_group(ALL_LIGHTS_GROUP)
+            .await?
+            .lights
+            .iter()
+            .map(|id| parse_numeric_id(id, "Light"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let lights = self
+            .run_concurrently(ids, |id| async move {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let light = if remaining == Duration::from_secs(0) {
+                    Err(HueError::Timeout)
+                } else {
+                    self.clone().with_timeout(remaining).get_light(id).await
+                };
+                (id, light)
+            })
+            .await;
+        let mut result = PartialLightResult::default();
+        for (id, light) in lights {
+            match light {
+                Ok(light) => result.lights.push(IdentifiedLight { id, light }),
+                Err(e) => result.errors.push((id, e)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Sends each command to its light, `with_max_concurrency` requests at
+    /// a time, and returns the answers in the order of `commands`. Fails as
+    /// a whole only without a username.
+    #[allow(clippy::type_complexity)]
+    pub async fn batch_set_light_states(
+        &self,
+        commands: &[(usize, CommandLight)],
+    ) -> Result<Vec<(usize, Result<Value, HueError>)>, HueError> {
+        self.username.as_ref().ok_or(HueError::NoUsername)?;
+        Ok(self
+            .run_concurrently(commands, |(id, cmd)| async move {
+                (*id, self.set_light_state(*id, cmd).await)
+            })
+            .await)
+    }
+
+    /// Awaits `f` of every item, up to `max_concurrency` at a time, keeping
+    /// the order of `items` in the results.
+    async fn run_concurrently<I: IntoIterator, F: Future>(
+        &self,
+        items: I,
+        f: impl Fn(I::Item) -> F,
+    ) -> Vec<F::Output> {
+        stream::iter(items)
+            .map(f)
+            .buffered(self.max_concurrency)
+            .collect()
+            .await
+    }
+
+    pub async fn get_all_lights_filtered<F: Fn(&Light) -> bool>(
+        &self,
+        predicate: F,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        let mut lights = self.get_all_lights().await?;
+        lights.retain(|l| predicate(&l.light));
+        Ok(lights)
+    }
+
+    pub async fn get_lights_by_model(
+        &self,
+        modelid: &str,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.modelid == modelid).await
+    }
+
+    pub async fn get_reachable_lights(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.state.reachable == Some(true))
+            .await
+    }
+
+    pub async fn get_lights_of_type(
+        &self,
+        light_type: &str,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.light_type == light_type)
+            .await
+    }
+
+    /// The first light whose name contains `name`, ignoring case. Like
+    /// `find_lights_by_name_prefix`, this fetches every light in one
+    /// request and searches them locally.
+    pub async fn find_light_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<IdentifiedLight>, HueError> {
+        let name = name.to_lowercase();
+        Ok(self
+            .get_all_lights()
+            .await?
+            .into_iter()
+            .find(|l| l.light.name.to_lowercase().contains(&name)))
+    }
+
+    /// Lights whose name starts with `prefix`, ignoring case.
+    pub async fn find_lights_by_name_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        let prefix = prefix.to_lowercase();
+        Ok(self
+            .get_all_lights()
+            .await?
+            .into_iter()
+            .filter(|l| l.light.name.to_lowercase().starts_with(&prefix))
+            .collect())
+    }
+
+    /// The `/lights` answer as sent by the bridge, without decoding it.
+    pub async fn get_all_lights_raw(&self) -> Result<Vec<u8>, HueError> {
+        self.get_raw("lights").await
+    }
+
+    /// Dimmest first, see `IdentifiedLight::cmp_by_brightness`.
+    pub async fn get_all_lights_sorted_by_brightness(
+        &self,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        let mut lights = self.get_all_lights().await?;
+        util::sort_lights_by_brightness(&mut lights);
+        Ok(lights)
+    }
+
+    pub async fn get_light_reachability(&self) -> Result<HashMap<usize, bool>, HueError> {
+        let resp: BTreeMap<String, LightReachability> = self.get("lights").await?;
+        resp.into_iter()
+            .filter_map(|(id, light)| light.state.reachable.map(|r| (id, r)))
+            .map(|(id, reachable)| Ok((parse_numeric_id(&id, "Light")?, reachable)))
+            .collect()
+    }
+
+    pub async fn get_light(&self, id: usize) -> Result<Light, HueError> {
+        self.get(&format!("lights/{}", id)).await
+    }
+
+    pub async fn get_light_new(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        let resp: HashMap<String, Value> = self.get("lights/new").await?;
+        let mut lights = vec![];
+        for id in new_light_ids(resp)? {
+            lights.push(IdentifiedLight {
+                id,
+                light: self.get_light(id).await?,
+            });
+        }
+        Ok(lights)
+    }
+
+    pub async fn search_for_new_lights(&self, device_ids: Option<&[&str]>) -> Result<(), HueError> {
+        let _: Value = self
+            .post(
+                "lights",
+                &LightSearch {
+                    deviceid: device_ids,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_new_lights(&self) -> Result<NewLightsResponse, HueError> {
+        self.get("lights/new").await
+    }
+
+    pub async fn set_light_name(&self, id: usize, name: &str) -> Result<(), HueError> {
+        check_name(name)?;
+        let _: Value = self.put(&format!("lights/{}", id), &Name { name }).await?;
+        Ok(())
+    }
+
+    pub async fn rename_light(&self, id: usize, name: &str) -> Result<(), HueError> {
+        self.set_light_name(id, name).await
+    }
+
+    pub async fn delete_light(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("lights/{}", id)).await
+    }
+
+    pub async fn set_light_state(
+        &self,
+        light: usize,
+        command: &CommandLight,
+    ) -> Result<Value, HueError> {
+        command.validate()?;
+        self.set_light_state_unchecked(light, command).await
+    }
+
+    pub async fn set_light_state_unchecked(
+        &self,
+        light: usize,
+        command: &CommandLight,
+    ) -> Result<Value, HueError> {
+        self.put_command(&format!("lights/{}/state", light), command)
+            .await
+    }
+
+    /// Blinks the light once, e.g. to find which bulb has this id. The
+    /// bridge puts `alert` back to `none` by itself.
+    pub async fn flash_light(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_alert(Alert::Select);
+        self.set_light_state(id, &command).await?;
+        Ok(())
+    }
+
+    /// Blinks the light for 15 seconds, after which the bridge puts `alert`
+    /// back to `none`.
+    pub async fn flash_light_long(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_alert(Alert::LSelect);
+        self.set_light_state(id, &command).await?;
+        Ok(())
+    }
+
+    /// Cycles through the hue wheel, keeping brightness and saturation,
+    /// until `stop_color_loop`.
+    pub async fn start_color_loop(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_effect(Effect::ColorLoop);
+        self.set_light_state(id, &command).await?;
+        Ok(())
+    }
+
+    /// Sends `effect: none`; the light keeps the color the loop was at.
+    pub async fn stop_color_loop(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_effect(Effect::None);
+        self.set_light_state(id, &command).await?;
+        Ok(())
+    }
+
+    pub async fn flash_group(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_alert(Alert::Select);
+        self.set_group_state(id, &command.into()).await?;
+        Ok(())
+    }
+
+    pub async fn get_all_groups(&self) -> Result<Vec<IdentifiedGroup>, HueError> {
+        let resp: HashMap<String, Group> = self.get("groups").await?;
+        identify(resp, "Group", |id, group| IdentifiedGroup { id, group })
+    }
+
+    pub async fn get_group(&self, id: usize) -> Result<Group, HueError> {
+        self.get(&format!("groups/{}", id)).await
+    }
+
+    pub async fn create_group(&self, name: &str, lights: &[usize]) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: None,
+            class: None,
+        };
+        self.post_group(&body).await
+    }
+
+    /// Rooms hold each light at most once across all rooms.
+    pub async fn create_room(
+        &self,
+        name: &str,
+        lights: &[usize],
+        room_class: RoomClass,
+    ) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: Some("Room"),
+            class: Some(room_class.as_str()),
+        };
+        self.post_group(&body).await
+    }
+
+    /// Zones, unlike rooms, may share lights.
+    pub async fn create_zone(&self, name: &str, lights: &[usize]) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: Some("Zone"),
+            class: None,
+        };
+        self.post_group(&body).await
+    }
+
+    async fn post_group(&self, body: &NewGroup<'_>) -> Result<usize, HueError> {
+        parse_created_group_id(self.post("groups", body).await?)
+    }
+
+    pub async fn set_group_state(
+        &self,
+        id: usize,
+        command: &CommandGroup,
+    ) -> Result<Value, HueError> {
+        self.put(&format!("groups/{}/action", id), command).await
+    }
+
+    pub async fn set_all_lights(&self, cmd: CommandLight) -> Result<(), HueError> {
+        cmd.validate()?;
+        self.set_group_state(ALL_LIGHTS_GROUP, &cmd.into())
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn dim_all_lights(&self, bri: u8) -> Result<(), HueError> {
+        self.set_all_lights(CommandLight::default().with_bri(bri))
+            .await
+    }
+
+    pub async fn set_group_attributes(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        lights: Option<&[usize]>,
+    ) -> Result<Value, HueError> {
+        let body = GroupAttributes {
+            name,
+            lights: lights.map(light_ids),
+        };
+        self.put(&format!("groups/{}", id), &body).await
+    }
+
+    pub async fn update_group(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        lights: Option<&[usize]>,
+    ) -> Result<(), HueError> {
+        check_group_update(name, lights)?;
+        self.set_group_attributes(id, name, lights).await?;
+        Ok(())
+    }
+
+    pub async fn set_group_name(&self, id: usize, name: &str) -> Result<(), HueError> {
+        check_name(name)?;
+        let _: Value = self.put(&format!("groups/{}", id), &Name { name }).await?;
+        Ok(())
+    }
+
+    pub async fn delete_group(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("groups/{}", id)).await
+    }
+
+    /// See `Bridge::set_multiple_lights`.
+    pub async fn set_multiple_lights(
+        &self,
+        ids: &[usize],
+        command: CommandLight,
+    ) -> Result<(), HueError> {
+        command.validate()?;
+        let group = self.create_group(TEMPORARY_GROUP_NAME, ids).await?;
+        let result = self
+            .set_group_state(group, &command.into())
+            .await
+            .map(|_| ());
+        let cleanup = self.delete_group(group).await;
+        #[cfg(feature = "tracing")]
+        log_leftover_group(group, &result, &cleanup);
+        result.and(cleanup)
+    }
+
+    pub async fn get_all_scenes(&self) -> Result<BTreeMap<String, Scene>, HueError> {
+        self.get("scenes").await
+    }
+
+    pub async fn get_scenes_for_group(
+        &self,
+        group_id: usize,
+    ) -> Result<Vec<SceneSummary>, HueError> {
+        Ok(scenes_for_group(self.get_all_scenes().await?, group_id))
+    }
+
+    pub async fn get_scene(&self, id: &str) -> Result<SceneDetail, HueError> {
+        let scene: SceneWithLightStates = self.get(&format!("scenes/{}", id)).await?;
+        scene.into_detail()
+    }
+
+    pub async fn create_scene(&self, name: &str, lights: &[usize]) -> Result<String, HueError> {
+        let body = NewScene {
+            name,
+            lights: light_ids(lights),
+            recycle: false,
+        };
+        parse_created_id(self.post("scenes", &body).await?)
+    }
+
+    pub async fn set_scene_light_state(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+        command: &CommandLight,
+    ) -> Result<Value, HueError> {
+        self.put_command(
+            &format!("scenes/{}/lightstates/{}", scene_id, light_id),
+            command,
+        )
+        .await
+    }
+
+    pub async fn update_scene_light_state(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+        cmd: CommandLight,
+    ) -> Result<(), HueError> {
+        cmd.validate()?;
+        self.set_scene_light_state(scene_id, light_id, &cmd)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn recall_scene(&self, group_id: usize, scene_id: &str) -> Result<Value, HueError> {
+        self.put(
+            &format!("groups/{}/action", group_id),
+            &SceneRecall { scene: scene_id },
+        )
+        .await
+    }
+
+    pub async fn activate_scene(&self, group_id: usize, scene_id: &str) -> Result<(), HueError> {
+        self.recall_scene(group_id, scene_id).await.map(|_| ())
+    }
+
+    pub async fn store_current_scene(&self, scene_id: &str) -> Result<(), HueError> {
+        let body = SceneUpdate {
+            storelightstate: true,
+            ..Default::default()
+        };
+        self.put::<_, Value>(&format!("scenes/{}", scene_id), &body)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn append_light_to_scene(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+    ) -> Result<(), HueError> {
+        let mut lights = self.get_scene(scene_id).await?.scene.lights;
+        let light = light_id.to_string();
+        if lights.contains(&light) {
+            return Ok(());
+        }
+        lights.push(light);
+        let body = SceneUpdate {
+            lights: Some(lights),
+            storelightstate: true,
+        };
+        self.put::<_, Value>(&format!("scenes/{}", scene_id), &body)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_scene(&self, id: &str) -> Result<(), HueError> {
+        self.delete(&format!("scenes/{}", id)).await
+    }
+
+    pub async fn get_all_schedules(&self) -> Result<Vec<IdentifiedSchedule>, HueError> {
+        let resp: HashMap<String, Schedule> = self.get("schedules").await?;
+        identify(resp, "Schedule", |id, schedule| IdentifiedSchedule {
+            id,
+            schedule,
+        })
+    }
+
+    pub async fn get_schedule(&self, id: usize) -> Result<Schedule, HueError> {
+        self.get(&format!("schedules/{}", id)).await
+    }
+
+    pub async fn create_schedule(&self, schedule: &Schedule) -> Result<usize, HueError> {
+        check_localtime(&schedule.localtime)?;
+        let success = self.post("schedules", schedule).await?;
+        parse_numeric_id(&parse_created_id(success)?, "Schedule")
+    }
+
+    pub async fn update_schedule(&self, id: usize, schedule: &Schedule) -> Result<Value, HueError> {
+        check_localtime(&schedule.localtime)?;
+        self.put(&format!("schedules/{}", id), schedule).await
+    }
+
+    pub async fn delete_schedule(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("schedules/{}", id)).await
+    }
+
+    pub async fn get_all_sensors(&self) -> Result<Vec<IdentifiedSensor>, HueError> {
+        let resp: HashMap<String, Sensor> = self.get("sensors").await?;
+        identify(resp, "Sensor", |id, sensor| IdentifiedSensor { id, sensor })
+    }
+
+    pub async fn get_sensor(&self, id: usize) -> Result<Sensor, HueError> {
+        self.get(&format!("sensors/{}", id)).await
+    }
+
+    pub async fn create_sensor(&self, sensor: &NewSensor) -> Result<usize, HueError> {
+        let success = self.post("sensors", sensor).await?;
+        parse_numeric_id(&parse_created_id(success)?, "Sensor")
+    }
+
+    pub async fn update_sensor_name(&self, id: usize, name: &str) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}", id), &Name { name }).await
+    }
+
+    pub async fn set_sensor_config(
+        &self,
+        id: usize,
+        config: &SensorConfig,
+    ) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}/config", id), config).await
+    }
+
+    pub async fn set_sensor_state(
+        &self,
+        id: usize,
+        state: &BTreeMap<String, Value>,
+    ) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}/state", id), state).await
+    }
+
+    pub async fn update_sensor_state(
+        &self,
+        id: usize,
+        state: &SensorStatePatch,
+    ) -> Result<(), HueError> {
+        let _: Value = self.put(&format!("sensors/{}/state", id), state).await?;
+        Ok(())
+    }
+
+    pub async fn delete_sensor(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("sensors/{}", id)).await
+    }
+
+    pub async fn get_all_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let resp: HashMap<String, Rule> = self.get("rules").await?;
+        identify(resp, "Rule", |id, rule| IdentifiedRule { id, rule })
+    }
+
+    pub async fn get_rule(&self, id: usize) -> Result<Rule, HueError> {
+        self.get(&format!("rules/{}", id)).await
+    }
+
+    pub async fn create_rule(
+        &self,
+        name: &str,
+        conditions: &[RuleCondition],
+        actions: &[RuleAction],
+    ) -> Result<usize, HueError> {
+        let body = NewRule {
+            name,
+            conditions,
+            actions,
+        };
+        let success = self.post("rules", &body).await?;
+        parse_numeric_id(&parse_created_id(success)?, "Rule")
+    }
+
+    pub async fn update_rule(&self, id: usize, rule: &Rule) -> Result<Value, HueError> {
+        self.put(&format!("rules/{}", id), rule).await
+    }
+
+    pub async fn update_rule_status(&self, id: usize, enabled: bool) -> Result<(), HueError> {
+        self.put::<_, Value>(&format!("rules/{}", id), &RuleStatus::new(enabled))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_enabled_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let mut rules = self.get_all_rules().await?;
+        rules.retain(|r| r.rule.is_enabled());
+        Ok(rules)
+    }
+
+    pub async fn get_disabled_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let mut rules = self.get_all_rules().await?;
+        rules.retain(|r| !r.rule.is_enabled());
+        Ok(rules)
+    }
+
+    pub async fn delete_rule(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("rules/{}", id)).await
+    }
+
+    pub async fn get_all_resourcelinks(&self) -> Result<Vec<IdentifiedResourceLink>, HueError> {
+        let resp: HashMap<String, ResourceLink> = self.get("resourcelinks").await?;
+        identify(resp, "Resource link", |id, resourcelink| {
+            IdentifiedResourceLink { id, resourcelink }
+        })
+    }
+
+    pub async fn get_resourcelink(&self, id: usize) -> Result<ResourceLink, HueError> {
+        self.get(&format!("resourcelinks/{}", id)).await
+    }
+
+    pub async fn create_resourcelink(
+        &self,
+        name: &str,
+        description: &str,
+        links: &[&str],
+    ) -> Result<usize, HueError> {
+        check_links(links)?;
+        let body = NewResourceLink {
+            name,
+            description,
+            classid: RESOURCELINK_CLASSID,
+            links,
+        };
+        let success = self.post("resourcelinks", &body).await?;
+        parse_numeric_id(&parse_created_id(success)?, "Resource link")
+    }
+
+    pub async fn update_resourcelink(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        description: Option<&str>,
+        links: Option<&[&str]>,
+    ) -> Result<Value, HueError> {
+        if let Some(links) = links {
+            check_links(links)?;
+        }
+        let body = ResourceLinkAttributes {
+            name,
+            description,
+            links,
+        };
+        self.put(&format!("resourcelinks/{}", id), &body).await
+    }
+
+    pub async fn delete_resourcelink(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("resourcelinks/{}", id)).await
+    }
+
+    pub async fn get_capabilities(&self) -> Result<Capabilities, HueError> {
+        self.get("capabilities").await
+    }
+
+    pub async fn get_firmware_update_status(&self) -> Result<UpdateStatus, HueError> {
+        self.get_config()
+            .await?
+            .swupdate2
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no swupdate2".into(),
+            })
+    }
+
+    pub async fn install_firmware_update(&self) -> Result<(), HueError> {
+        self.trigger_software_update(true, false).await
+    }
+
+    pub async fn check_for_firmware_update(&self) -> Result<(), HueError> {
+        self.trigger_software_update(false, true).await
+    }
+
+    async fn trigger_software_update(
+        &self,
+        install: bool,
+        checkforupdate: bool,
+    ) -> Result<(), HueError> {
+        let body = SoftwareUpdate {
+            swupdate2: SoftwareUpdateTrigger {
+                install,
+                checkforupdate,
+            },
+        };
+        self.put::<_, Value>("config", &body).await?;
+        Ok(())
+    }
+
+    pub async fn get_timezone_list(&self) -> Result<Vec<String>, HueError> {
+        self.get("info/timezones").await
+    }
+
+    pub async fn get_config(&self) -> Result<BridgeConfig, HueError> {
+        self.get("config").await
+    }
+
+    pub async fn get_full_state(&self) -> Result<FullState, HueError> {
+        self.get("").await
+    }
+
+    pub async fn set_config(&self, patch: &BridgeConfigPatch) -> Result<Value, HueError> {
+        self.put("config", patch).await
+    }
+
+    /// See `Bridge::backup`.
+    pub async fn backup(&self) -> Result<Vec<u8>, HueError> {
+        check_backup(self.get_raw("config/backup").await?)
+    }
+
+    /// See `Bridge::restore`: the bridge reboots afterwards.
+    pub async fn restore(&self, data: &[u8]) -> Result<(), HueError> {
+        let url = url(
+            self.scheme,
+            &self.host(),
+            self.username.as_deref(),
+            "config/backup",
+        )?;
+        let part = reqwest::multipart::Part::bytes(data.to_vec())
+            .file_name("backup.zip")
+            .mime_str("application/zip")?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+        parse::<Value>(
+            self.send(self.client.post(&url[..]).multipart(form))
+                .await?,
+        )?;
+        Ok(())
+    }
+
+    /// **Irreversible**, see `Bridge::factory_reset`.
+    pub async fn factory_reset(&self) -> Result<(), HueError> {
+        self.put::<_, Value>("config", &FactoryReset { factoryreset: true })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_api_version(&self) -> Result<ApiVersion, HueError> {
+        self.get_config()
+            .await?
+            .apiversion
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no apiversion".into(),
+            })?
+            .parse()
+    }
+
+    pub async fn requires_api_version(&self, min: ApiVersion) -> Result<(), HueError> {
+        check_api_version(self.get_api_version().await?, min)
+    }
+
+    pub async fn get_zigbee_channel(&self) -> Result<u8, HueError> {
+        self.get_config()
+            .await?
+            .zigbeechannel
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no zigbeechannel".into(),
+            })
+    }
+
+    pub async fn set_zigbee_channel(&self, channel: u8) -> Result<(), HueError> {
+        check_zigbee_channel(channel)?;
+        self.set_config(&BridgeConfigPatch::default().with_zigbee_channel(channel))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn touchlink(&self) -> Result<(), HueError> {
+        let _: Value = self.put("config", &Touchlink { touchlink: true }).await?;
+        Ok(())
+    }
+
+    pub async fn get_whitelist(&self) -> Result<BTreeMap<String, WhitelistEntry>, HueError> {
+        Ok(self.get_config().await?.whitelist.unwrap_or_default())
+    }
+
+    pub async fn delete_user(&self, username_to_delete: &str) -> Result<(), HueError> {
+        self.delete(&format!("config/whitelist/{}", username_to_delete))
+            .await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.get(&url[..])).await?)
+    }
+
+    async fn get_raw(&self, path: &str) -> Result<Vec<u8>, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        Ok(self
+            .send_raw(self.client.get(&url[..]))
+            .await?
+            .bytes()
+            .await?
+            .to_vec())
+    }
+
+    async fn put<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.put(&url[..]).json(body)).await?)
+    }
+
+    async fn put_command(&self, path: &str, command: &CommandLight) -> Result<Value, HueError> {
+        self.put(path, command).await
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.post(&url[..]).json(body)).await?)
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        let _: Value = parse(self.send(self.client.delete(&url[..])).await?)?;
+        Ok(())
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, HueError> {
+        let response = self.send_raw(request).await?;
+        #[cfg(feature = "tracing")]
+        {
+            let url = response.url().clone();
+            let body = response.bytes().await?;
+            log_response_body(&url, &body);
+            Ok(serde_json::from_slice(&body)?)
+        }
+        #[cfg(not(feature = "tracing"))]
+        Ok(response.json().await?)
+    }
+
+    async fn send_raw(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, HueError> {
+        let request = match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        let request = request.build()?;
+        #[cfg(feature = "tracing")]
+        log_request(
+            request.method(),
+            request.url(),
+            request.body().and_then(|b| b.as_bytes()),
+            self.username.as_deref(),
+        );
+        let response = self.client.execute(request).await;
+        #[cfg(feature = "tracing")]
+        log_response(response.as_ref().map(|r| (r.status(), r.content_length())));
+        Ok(response?)
+    }
+}
@@ -0,0 +1,181 @@
+//! `hue`, a command line client. The bridge comes from the credentials file
+//! written by `hue register`, else from `HUE_BRIDGE_IP` and `HUE_USERNAME`.
+
+#![allow(clippy::result_large_err)]
+
+use std::collections::BTreeMap;
+
+use clap::{Parser, Subcommand};
+use hueclient::bridge::{credentials_path, Bridge, CommandLight};
+use hueclient::discovery::discover_bridges;
+use hueclient::HueError;
+use serde::Serialize;
+
+#[derive(Parser)]
+#[command(name = "hue", about = "Control Philips Hue lights")]
+struct Cli {
+    /// Print JSON instead of tables.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the bridges on the network.
+    Discover,
+    /// Register a user, pressing the link button when asked, and save the
+    /// credentials.
+    Register { devicetype: String },
+    /// List the lights.
+    Lights,
+    /// Change the state of a light.
+    Set {
+        id: usize,
+        #[arg(long, conflicts_with = "off")]
+        on: bool,
+        #[arg(long)]
+        off: bool,
+        #[arg(long)]
+        bri: Option<u8>,
+        #[arg(long)]
+        hue: Option<u16>,
+        #[arg(long)]
+        sat: Option<u8>,
+        #[arg(long, value_parser = clap::value_parser!(u16).range(153..=500))]
+        ct: Option<u16>,
+    },
+    /// List the groups.
+    Groups,
+    /// List the scenes.
+    Scenes,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(&cli) {
+        eprintln!("Error: {}", err);
+        std::process::exit(2)
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), HueError> {
+    match &cli.command {
+        Command::Discover => {
+            let bridges = discover_bridges();
+            if cli.json {
+                print_json(&bridges)?;
+            } else {
+                bridges.iter().for_each(|ip| println!("{}", ip));
+            }
+        }
+        Command::Register { devicetype } => {
+            let bridge = Bridge::load_or_register(devicetype)?;
+            println!("{}", bridge);
+        }
+        Command::Lights => {
+            let lights = bridge()?.get_all_lights()?;
+            if cli.json {
+                return print_json(
+                    &lights
+                        .iter()
+                        .map(|l| (l.id, &l.light))
+                        .collect::<BTreeMap<_, _>>(),
+                );
+            }
+            println!("id name                 on  bri   hue sat  ct");
+            for l in lights.iter() {
+                let state = &l.light.state;
+                println!(
+                    "{:2} {:20} {:3} {:3} {:5} {:3} {:3}",
+                    l.id,
+                    l.light.name,
+                    if state.on { "on" } else { "off" },
+                    state.bri.unwrap_or(0),
+                    state.hue.unwrap_or(0),
+                    state.sat.unwrap_or(0),
+                    state.ct.unwrap_or(0),
+                );
+            }
+        }
+        Command::Set {
+            id,
+            on,
+            off,
+            bri,
+            hue,
+            sat,
+            ct,
+        } => {
+            let command = CommandLight {
+                on: if *on {
+                    Some(true)
+                } else if *off {
+                    Some(false)
+                } else {
+                    None
+                },
+                bri: *bri,
+                hue: *hue,
+                sat: *sat,
+                ct: *ct,
+                ..CommandLight::default()
+            };
+            let response = bridge()?.set_light_state(*id, &command)?;
+            if cli.json {
+                print_json(&response)?;
+            }
+        }
+        Command::Groups => {
+            let groups = bridge()?.get_all_groups()?;
+            if cli.json {
+                return print_json(
+                    &groups
+                        .iter()
+                        .map(|g| (g.id, &g.group))
+                        .collect::<BTreeMap<_, _>>(),
+                );
+            }
+            for g in groups.iter() {
+                println!(
+                    "{:2} {:20} {}",
+                    g.id,
+                    g.group.name,
+                    g.group.lights.join(",")
+                );
+            }
+        }
+        Command::Scenes => {
+            let scenes = bridge()?.get_all_scenes()?;
+            if cli.json {
+                return print_json(&scenes);
+            }
+            for (id, scene) in scenes.iter() {
+                println!("{:16} {:20} {}", id, scene.name, scene.lights.join(","));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn bridge() -> Result<Bridge, HueError> {
+    let path = credentials_path()?;
+    if path.exists() {
+        return Bridge::from_file(&path);
+    }
+    match (
+        std::env::var("HUE_BRIDGE_IP"),
+        std::env::var("HUE_USERNAME"),
+    ) {
+        (Ok(ip), Ok(username)) => Bridge::builder().ip(&ip).username(&username).build(),
+        _ => Err(HueError::InvalidArgument {
+            msg: "run `hue register` or set HUE_BRIDGE_IP and HUE_USERNAME".into(),
+        }),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), HueError> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
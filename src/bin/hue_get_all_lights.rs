@@ -12,18 +12,18 @@ fn main() {
     match bridge.get_all_lights() {
         Ok(lights) => {
             println!("id name                 on    bri   hue sat temp  x      y");
-            for ref l in lights.iter() {
+            for l in lights.iter() {
                 println!(
                     "{:2} {:20} {:5} {:3} {:5} {:3} {:4}K {:4} {:4}",
                     l.id,
                     l.light.name,
                     if l.light.state.on { "on" } else { "off" },
-                    if l.light.state.bri.is_some() {l.light.state.bri.unwrap()} else {0},
-                    if l.light.state.hue.is_some() {l.light.state.hue.unwrap()} else {0},
-                    if l.light.state.sat.is_some() {l.light.state.sat.unwrap()} else {0},
-                    if l.light.state.ct.is_some() {l.light.state.ct.map(|k| if k != 0 { 1000000u32 / (k as u32) } else { 0 }).unwrap()} else {0},
-                    if l.light.state.xy.is_some() {l.light.state.xy.unwrap().0} else {0.0},
-                    if l.light.state.xy.is_some() {l.light.state.xy.unwrap().1} else {0.0},
+                    l.light.state.bri.unwrap_or(0),
+                    l.light.state.hue.unwrap_or(0),
+                    l.light.state.sat.unwrap_or(0),
+                    l.light.state.ct.map(|k| if k != 0 { 1000000u32 / (k as u32) } else { 0 }).unwrap_or(0),
+                    l.light.state.xy.map(|xy| xy.0).unwrap_or(0.0),
+                    l.light.state.xy.map(|xy| xy.1).unwrap_or(0.0),
                 );
             }
         }
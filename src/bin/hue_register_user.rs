@@ -1,5 +1,5 @@
 extern crate hueclient;
-use hueclient::HueError;
+use hueclient::{HueError, HueErrorCode};
 use std::env;
 
 #[allow(while_true)]
@@ -19,7 +19,7 @@ fn main() {
                     println!("{}", r);
                     break;
                 }
-                Err(HueError::BridgeError { code, .. }) if code == 101 => {
+                Err(HueError::BridgeError { code: HueErrorCode::LinkButtonNotPressed, .. }) => {
                     println!("Push the bridge button");
                     std::thread::sleep(::std::time::Duration::from_secs(5));
                 }
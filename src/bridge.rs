@@ -1,248 +1,3669 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
 use std::collections::HashMap;
+use std::net::IpAddr;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
+#[cfg(feature = "serde")]
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "serde")]
+use std::time::Instant;
 
-use reqwest;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
 use serde_json::Value;
 
 use crate::*;
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// Fields a light does not report, e.g. the colors of a dimmable light,
+/// are left out again when serializing, as in the bridge output.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LightState {
     pub on: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub bri: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub hue: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub sat: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub ct: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub xy: Option<(f32, f32)>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub effect: Option<Effect>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub alert: Option<Alert>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub colormode: Option<ColorMode>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub reachable: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The color a light shows, in the color mode it follows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LightColor {
+    Hs { hue: u16, sat: u8, bri: u8 },
+    Xy { x: f32, y: f32, bri: u8 },
+    Ct { mireds: u16, bri: u8 },
+}
+
+const MIN_BRI: u8 = 1;
+const MAX_BRI: u8 = 254;
+
+impl LightState {
+    /// `bri` as a percentage, the inverse of `CommandLight::brightness_percent`;
+    /// 0 for lights without brightness.
+    pub fn brightness_percent(&self) -> f32 {
+        match self.bri {
+            Some(bri) => {
+                let bri = bri.clamp(MIN_BRI, MAX_BRI) - MIN_BRI;
+                bri as f32 * 100.0 / (MAX_BRI - MIN_BRI) as f32
+            }
+            None => 0.0,
+        }
+    }
+
+    /// `None` for unreachable lights, and for lights without a color mode
+    /// or missing the fields of theirs.
+    pub fn color(&self) -> Option<LightColor> {
+        if self.reachable == Some(false) {
+            return None;
+        }
+        let bri = self.bri?;
+        match self.colormode? {
+            ColorMode::Hs => Some(LightColor::Hs {
+                hue: self.hue?,
+                sat: self.sat?,
+                bri,
+            }),
+            ColorMode::Xy => {
+                let (x, y) = self.xy?;
+                Some(LightColor::Xy { x, y, bri })
+            }
+            ColorMode::Ct => Some(LightColor::Ct {
+                mireds: self.ct?,
+                bri,
+            }),
+        }
+    }
+
+    pub fn color_temperature_kelvin(&self) -> Option<color::Kelvin> {
+        self.ct.map(|ct| color::Mireds(ct).into())
+    }
+
+    pub fn is_off(&self) -> bool {
+        !self.on
+    }
+
+    /// Below about 3000K; false unless the light is in color temperature
+    /// mode.
+    pub fn is_warm_white(&self) -> bool {
+        self.white_ct().is_some_and(|ct| ct > WARM_WHITE_MIREDS)
+    }
+
+    /// Above 4000K; false unless the light is in color temperature mode.
+    pub fn is_cool_white(&self) -> bool {
+        self.white_ct().is_some_and(|ct| ct < COOL_WHITE_MIREDS)
+    }
+
+    /// In `hs` or `xy` mode with a saturation of about a quarter or more,
+    /// rather than near white.
+    pub fn is_colorful(&self) -> bool {
+        let sat = match (self.colormode, self.sat, self.xy) {
+            (Some(ColorMode::Hs), Some(sat), _) => sat,
+            (Some(ColorMode::Xy), _, Some((x, y))) => {
+                let (r, g, b) = color::xy_to_rgb(x, y);
+                color::rgb_to_hsb(r, g, b).1
+            }
+            _ => return false,
+        };
+        sat >= COLORFUL_MIN_SAT
+    }
+
+    fn white_ct(&self) -> Option<u16> {
+        match self.colormode {
+            None | Some(ColorMode::Ct) => self.ct,
+            _ => None,
+        }
+    }
+}
+
+const WARM_WHITE_MIREDS: u16 = 330;
+const COOL_WHITE_MIREDS: u16 = 250;
+const COLORFUL_MIN_SAT: u8 = 64;
+
+/// Which of the color fields of a `LightState` the light is currently
+/// following.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[non_exhaustive]
+pub enum ColorMode {
+    Hs,
+    Xy,
+    Ct,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Light {
     pub name: String,
+    /// E.g. "Extended color light", "Color temperature light" or
+    /// "Dimmable light".
+    #[cfg_attr(feature = "serde", serde(rename = "type", default))]
+    pub light_type: String,
     pub modelid: String,
     pub swversion: String,
     pub uniqueid: String,
     pub state: LightState,
 }
 
+impl std::fmt::Display for Light {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reachable = match self.state.reachable {
+            Some(true) => "reachable",
+            Some(false) => "unreachable",
+            None => "unknown",
+        };
+        write!(f, "{} ({})", self.name, reachable)
+    }
+}
+
+impl Light {
+    /// Takes `hue`/`sat` and `xy`: "Extended color light" and "Color light".
+    pub fn is_color_capable(&self) -> bool {
+        self.light_type.to_lowercase().ends_with("color light")
+    }
+
+    /// Takes `ct`: "Extended color light" and "Color temperature light".
+    pub fn is_ct_capable(&self) -> bool {
+        let light_type = self.light_type.to_lowercase();
+        light_type == "extended color light" || light_type == "color temperature light"
+    }
+
+    /// Takes `bri` but no color at all.
+    pub fn is_dimmable_only(&self) -> bool {
+        self.light_type.eq_ignore_ascii_case("dimmable light")
+    }
+
+    /// Lists the fields of `cmd` this light does not take, going by its
+    /// type. Lights of an unknown type are assumed to take everything.
+    pub fn supports_command(&self, cmd: &CommandLight) -> Result<(), Vec<String>> {
+        if self.light_type.is_empty() {
+            return Ok(());
+        }
+        let color = self.is_color_capable();
+        let dimmable = color || self.is_ct_capable() || self.is_dimmable_only();
+        let colorloop = cmd.effect.as_deref() == Some("colorloop");
+        let unsupported: Vec<String> = [
+            ("bri", cmd.bri.is_some() && !dimmable),
+            ("hue", cmd.hue.is_some() && !color),
+            ("sat", cmd.sat.is_some() && !color),
+            ("xy", cmd.xy.is_some() && !color),
+            ("ct", cmd.ct.is_some() && !self.is_ct_capable()),
+            ("effect", colorloop && !color),
+        ]
+        .iter()
+        .filter(|(_, unsupported)| *unsupported)
+        .map(|(field, _)| field.to_string())
+        .collect();
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(unsupported)
+        }
+    }
+
+    /// Looks the gamut up from the model id, for the Philips models known
+    /// so far; `None` for other lights, color capable or not.
+    pub fn color_gamut(&self) -> Option<color::ColorGamut> {
+        color::ColorGamut::for_model(&self.modelid)
+    }
+}
+
+/// Lights compare and hash by id only, so that two snapshots of the same
+/// light in different states are the same key.
 #[derive(Debug, Clone)]
 pub struct IdentifiedLight {
     pub id: usize,
     pub light: Light,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CommandLight {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub on: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub bri: Option<u8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hue: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sat: Option<u8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ct: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub xy: Option<(f32, f32)>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub transitiontime: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub alert: Option<String>,
+impl PartialEq for IdentifiedLight {
+    fn eq(&self, other: &IdentifiedLight) -> bool {
+        self.id == other.id
+    }
 }
 
-impl Default for CommandLight {
-    fn default() -> CommandLight {
-        CommandLight {
-            on: None,
-            bri: None,
-            hue: None,
-            sat: None,
-            transitiontime: None,
-            ct: None,
-            xy: None,
-            alert: None,
-        }
+impl Eq for IdentifiedLight {}
+
+impl std::hash::Hash for IdentifiedLight {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
     }
 }
 
-impl CommandLight {
-    pub fn on(self) -> CommandLight {
-        CommandLight {
-            on: Some(true),
-            ..self
-        }
+impl std::fmt::Display for IdentifiedLight {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.id, self.light)
     }
-    pub fn off(self) -> CommandLight {
-        CommandLight {
-            on: Some(false),
-            ..self
-        }
+}
+
+impl IdentifiedLight {
+    /// Orders by `bri`, then off before on. `LightState` itself is not
+    /// `Ord`: that would disagree with its field by field equality.
+    pub fn cmp_by_brightness(&self, other: &IdentifiedLight) -> std::cmp::Ordering {
+        let key = |l: &IdentifiedLight| (l.light.state.bri.unwrap_or(0), l.light.state.on);
+        key(self).cmp(&key(other))
     }
-    pub fn with_bri(self, b: u8) -> CommandLight {
-        CommandLight {
-            bri: Some(b),
-            ..self
-        }
+
+    /// Rejects fields the light does not take, see `Light::supports_command`.
+    #[cfg(feature = "serde")]
+    pub fn set_state(&self, bridge: &Bridge, cmd: CommandLight) -> Result<Value, HueError> {
+        self.light
+            .supports_command(&cmd)
+            .map_err(|fields| HueError::InvalidArgument {
+                msg: format!("light {} does not take {}", self.id, fields.join(", ")),
+            })?;
+        bridge.set_light_state(self.id, &cmd)
     }
-    pub fn with_hue(self, h: u16) -> CommandLight {
-        CommandLight {
-            hue: Some(h),
-            ..self
-        }
+
+    #[cfg(feature = "serde")]
+    pub fn refresh(&mut self, bridge: &Bridge) -> Result<(), HueError> {
+        self.light = bridge.get_light(self.id)?;
+        Ok(())
     }
-    pub fn with_sat(self, s: u8) -> CommandLight {
-        CommandLight {
-            sat: Some(s),
-            ..self
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NewLight {
+    pub name: String,
+}
+
+/// What `/lights/new` reports: the time of the last scan (`"active"` while
+/// a scan is running, `"none"` if none ran) and the names of the lights it
+/// found, by id.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NewLightsResponse {
+    pub lastscan: String,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub lights: BTreeMap<String, NewLight>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Group {
+    pub name: String,
+    pub lights: Vec<String>,
+    /// The last command sent to the group, not the state of its lights: in
+    /// a group with mixed states it matches none of them. See `state` for
+    /// what the lights actually are.
+    pub action: LightState,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub group_type: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub state: Option<GroupState>,
+    /// The room class, for rooms.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub class: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GroupState {
+    pub all_on: bool,
+    pub any_on: bool,
+}
+
+/// The room classes the bridge knows, which pick the room icon in the apps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RoomClass {
+    LivingRoom,
+    Kitchen,
+    Dining,
+    Bedroom,
+    KidsBedroom,
+    Bathroom,
+    Nursery,
+    Recreation,
+    Office,
+    Gym,
+    Hallway,
+    Toilet,
+    FrontDoor,
+    Garage,
+    Terrace,
+    Garden,
+    Driveway,
+    Carport,
+    Home,
+    Downstairs,
+    Upstairs,
+    TopFloor,
+    Attic,
+    GuestRoom,
+    Staircase,
+    Lounge,
+    ManCave,
+    Computer,
+    Studio,
+    Music,
+    Tv,
+    Reading,
+    Closet,
+    Storage,
+    LaundryRoom,
+    Balcony,
+    Porch,
+    Barbecue,
+    Pool,
+    Other,
+}
+
+impl RoomClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomClass::LivingRoom => "Living room",
+            RoomClass::Kitchen => "Kitchen",
+            RoomClass::Dining => "Dining",
+            RoomClass::Bedroom => "Bedroom",
+            RoomClass::KidsBedroom => "Kids bedroom",
+            RoomClass::Bathroom => "Bathroom",
+            RoomClass::Nursery => "Nursery",
+            RoomClass::Recreation => "Recreation",
+            RoomClass::Office => "Office",
+            RoomClass::Gym => "Gym",
+            RoomClass::Hallway => "Hallway",
+            RoomClass::Toilet => "Toilet",
+            RoomClass::FrontDoor => "Front door",
+            RoomClass::Garage => "Garage",
+            RoomClass::Terrace => "Terrace",
+            RoomClass::Garden => "Garden",
+            RoomClass::Driveway => "Driveway",
+            RoomClass::Carport => "Carport",
+            RoomClass::Home => "Home",
+            RoomClass::Downstairs => "Downstairs",
+            RoomClass::Upstairs => "Upstairs",
+            RoomClass::TopFloor => "Top floor",
+            RoomClass::Attic => "Attic",
+            RoomClass::GuestRoom => "Guest room",
+            RoomClass::Staircase => "Staircase",
+            RoomClass::Lounge => "Lounge",
+            RoomClass::ManCave => "Man cave",
+            RoomClass::Computer => "Computer",
+            RoomClass::Studio => "Studio",
+            RoomClass::Music => "Music",
+            RoomClass::Tv => "TV",
+            RoomClass::Reading => "Reading",
+            RoomClass::Closet => "Closet",
+            RoomClass::Storage => "Storage",
+            RoomClass::LaundryRoom => "Laundry room",
+            RoomClass::Balcony => "Balcony",
+            RoomClass::Porch => "Porch",
+            RoomClass::Barbecue => "Barbecue",
+            RoomClass::Pool => "Pool",
+            RoomClass::Other => "Other",
         }
     }
-    pub fn with_ct(self, c: u16) -> CommandLight {
-        CommandLight {
-            ct: Some(c),
-            ..self
-        }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentifiedGroup {
+    pub id: usize,
+    pub group: Group,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Scene {
+    pub name: String,
+    pub lights: Vec<String>,
+    pub owner: String,
+    pub recycle: bool,
+    /// The group of a "GroupScene"; light scenes have none.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub group: Option<String>,
+    /// Locked scenes are used by a rule or schedule and cannot be deleted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneSummary {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub recycle: bool,
+    pub locked: bool,
+}
+
+/// A scene as returned on its own, with the state it gives each light.
+#[derive(Debug, Clone)]
+pub struct SceneDetail {
+    pub scene: Scene,
+    /// Keyed by light id.
+    pub light_states: BTreeMap<usize, LightState>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+pub(crate) struct SceneWithLightStates {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub scene: Scene,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lightstates: BTreeMap<String, LightState>,
+}
+
+#[cfg(feature = "serde")]
+impl SceneWithLightStates {
+    pub(crate) fn into_detail(self) -> Result<SceneDetail, HueError> {
+        let light_states = self
+            .lightstates
+            .into_iter()
+            .map(|(id, state)| Ok((parse_numeric_id(&id, "Light")?, state)))
+            .collect::<Result<_, HueError>>()?;
+        Ok(SceneDetail {
+            scene: self.scene,
+            light_states,
+        })
     }
-    pub fn with_xy(self, x: f32, y: f32) -> CommandLight {
-        CommandLight {
-            xy: Some((x, y)),
-            ..self
-        }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScheduleCommand {
+    pub address: String,
+    pub method: String,
+    pub body: Value,
+}
+
+/// The bridge runs schedule commands as the user of the address, so these
+/// need a bridge with a username.
+#[cfg(feature = "serde")]
+impl ScheduleCommand {
+    pub fn for_light(
+        bridge: &Bridge,
+        light_id: usize,
+        cmd: CommandLight,
+    ) -> Result<ScheduleCommand, HueError> {
+        ScheduleCommand::put(bridge, &format!("lights/{}/state", light_id), &cmd)
     }
-    pub fn alert(self) -> CommandLight {
-        CommandLight {
-            alert: Some("select".into()),
-            ..self
-        }
+
+    pub fn for_group(
+        bridge: &Bridge,
+        group_id: usize,
+        cmd: CommandLight,
+    ) -> Result<ScheduleCommand, HueError> {
+        ScheduleCommand::put(
+            bridge,
+            &format!("groups/{}/action", group_id),
+            &CommandGroup::from(cmd),
+        )
+    }
+
+    pub fn for_scene(
+        bridge: &Bridge,
+        group_id: usize,
+        scene_id: &str,
+    ) -> Result<ScheduleCommand, HueError> {
+        ScheduleCommand::put(
+            bridge,
+            &format!("groups/{}/action", group_id),
+            &SceneRecall { scene: scene_id },
+        )
+    }
+
+    fn put(
+        bridge: &Bridge,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<ScheduleCommand, HueError> {
+        let username = bridge.username().ok_or(HueError::NoUsername)?;
+        Ok(ScheduleCommand {
+            address: format!("/api/{}/{}", username, path),
+            method: "PUT".to_string(),
+            body: serde_json::to_value(body)?,
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct Bridge {
-    pub ip: std::net::IpAddr,
-    pub username: Option<String>,
-    client: reqwest::blocking::Client,
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Schedule {
+    pub name: String,
+    pub description: String,
+    pub command: ScheduleCommand,
+    pub localtime: String,
+    pub status: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub autodelete: bool,
 }
 
-impl Bridge {
-    pub fn for_ip(ip: std::net::IpAddr) -> Bridge {
-        Bridge {
-            ip,
-            username: None,
-            client: reqwest::blocking::Client::new(),
+#[cfg(feature = "serde")]
+impl Schedule {
+    /// Checks `s` against the bridge time patterns: absolute time
+    /// (`2020-01-31T07:30:00`), weekly recurring time (`W127/T07:30:00`),
+    /// timers (`PT00:10:00`) and recurring timers (`R/PT00:10:00`,
+    /// `R05/PT00:10:00`), all optionally randomized with `A00:05:00`.
+    pub fn validate_localtime(s: &str) -> bool {
+        let re = Regex::new(concat!(
+            r"^(\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}",
+            r"|W(\d{3})/T\d{2}:\d{2}:\d{2}",
+            r"|(R(\d{2})?/)?PT\d{2}:\d{2}:\d{2})",
+            r"(A\d{2}:\d{2}:\d{2})?$"
+        ))
+        .unwrap();
+        match re.captures(s) {
+            Some(caps) => caps
+                .get(2)
+                .map(|days| days.as_str().parse::<u8>().is_ok_and(|d| d <= 127))
+                .unwrap_or(true),
+            None => false,
         }
     }
+}
 
-    #[allow(dead_code)]
-    pub fn discover() -> Option<Bridge> {
-        disco::discover_hue_bridge().ok().map(|ip| Bridge {
-            ip,
-            username: None,
-            client: reqwest::blocking::Client::new(),
-        })
-    }
+/// A schedule `localtime`, built from its parts rather than written by
+/// hand. Randomized times are not covered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScheduleTime {
+    /// `YYYY-MM-DDThh:mm:ss`.
+    Once(NaiveDateTime),
+    /// `W<bbb>/Thh:mm:ss`; bit 6 of `weekdays` is Monday and bit 0 Sunday.
+    Weekly { weekdays: u8, time: NaiveTime },
+    /// `PThh:mm:ss`, once after the interval.
+    Timer(Duration),
+    /// `R/PThh:mm:ss`, or `R<nn>/PThh:mm:ss` when `times` is set.
+    RecurringTimer {
+        interval: Duration,
+        times: Option<u32>,
+    },
+}
 
-    pub fn discover_required() -> Bridge {
-        Bridge::discover().unwrap_or_else(|| panic!("No bridge found!"))
+/// Timer intervals are written as `hh:mm:ss` with at most 23 hours.
+const MAX_TIMER: Duration = Duration::from_secs(23 * 3600 + 59 * 60 + 59);
+
+impl ScheduleTime {
+    pub fn once_at(datetime: &NaiveDateTime) -> Self {
+        ScheduleTime::Once(*datetime)
     }
 
-    pub fn with_user(self, username: String) -> Bridge {
-        Bridge {
-            username: Some(username),
-            ..self
+    pub fn daily_at(time: NaiveTime) -> Self {
+        ScheduleTime::Weekly {
+            weekdays: 127,
+            time,
         }
     }
 
-    pub fn register_user(&mut self, devicetype: &str) -> Result<String, HueError> {
-        #[derive(Serialize, Deserialize)]
-        struct PostApi {
-            devicetype: String,
+    pub fn after(interval: Duration) -> Self {
+        ScheduleTime::Timer(interval)
+    }
+
+    pub fn every(interval: Duration) -> Self {
+        ScheduleTime::RecurringTimer {
+            interval,
+            times: None,
         }
-        #[derive(Serialize, Deserialize)]
-        struct Success {
-            success: Username,
+    }
+
+    /// `n` is capped at 99, the most the bridge supports.
+    pub fn every_n_times(interval: Duration, n: u32) -> Self {
+        ScheduleTime::RecurringTimer {
+            interval,
+            times: Some(n.clamp(1, 99)),
         }
-        #[derive(Serialize, Deserialize)]
-        struct Username {
-            username: String,
+    }
+
+    /// Intervals are capped at 23:59:59 and rounded down to the second.
+    pub fn to_localtime_string(&self) -> String {
+        match *self {
+            ScheduleTime::Once(dt) => format!(
+                "{:04}-{:02}-{:02}T{}",
+                dt.year(),
+                dt.month(),
+                dt.day(),
+                format_time(dt.hour(), dt.minute(), dt.second())
+            ),
+            ScheduleTime::Weekly { weekdays, time } => format!(
+                "W{:03}/T{}",
+                weekdays & 127,
+                format_time(time.hour(), time.minute(), time.second())
+            ),
+            ScheduleTime::Timer(interval) => format!("PT{}", format_interval(interval)),
+            ScheduleTime::RecurringTimer { interval, times } => match times {
+                Some(n) => format!("R{:02}/PT{}", n.clamp(1, 99), format_interval(interval)),
+                None => format!("R/PT{}", format_interval(interval)),
+            },
         }
-        let obtain = PostApi {
-            devicetype: devicetype.to_string(),
+    }
+
+    pub fn from_localtime_string(s: &str) -> Result<ScheduleTime, HueError> {
+        let invalid = || HueError::InvalidArgument {
+            msg: format!("unsupported schedule time {}", s),
         };
-        let url = format!("http://{}/api", self.ip);
-        let success: Success =
-            self.parse(self.client.post(&url[..]).json(&obtain).send()?.json()?)?;
+        let re = Regex::new(concat!(
+            r"^(?:(\d{4})-(\d{2})-(\d{2})T(\d{2}):(\d{2}):(\d{2})",
+            r"|W(\d{3})/T(\d{2}):(\d{2}):(\d{2})",
+            r"|(R(\d{2})?/)?PT(\d{2}):(\d{2}):(\d{2}))$"
+        ))
+        .unwrap();
+        let caps = re.captures(s).ok_or_else(invalid)?;
+        let num = |i: usize| -> u32 { caps.get(i).map_or(0, |m| m.as_str().parse().unwrap_or(0)) };
+        if caps.get(1).is_some() {
+            let date =
+                NaiveDate::from_ymd_opt(num(1) as i32, num(2), num(3)).ok_or_else(invalid)?;
+            let time = NaiveTime::from_hms_opt(num(4), num(5), num(6)).ok_or_else(invalid)?;
+            Ok(ScheduleTime::Once(NaiveDateTime::new(date, time)))
+        } else if caps.get(7).is_some() {
+            let weekdays = num(7);
+            if weekdays > 127 {
+                return Err(invalid());
+            }
+            let time = NaiveTime::from_hms_opt(num(8), num(9), num(10)).ok_or_else(invalid)?;
+            Ok(ScheduleTime::Weekly {
+                weekdays: weekdays as u8,
+                time,
+            })
+        } else {
+            if num(13) > 23 || num(14) > 59 || num(15) > 59 {
+                return Err(invalid());
+            }
+            let interval = Duration::from_secs((num(13) * 3600 + num(14) * 60 + num(15)) as u64);
+            match (caps.get(11), caps.get(12)) {
+                (None, _) => Ok(ScheduleTime::Timer(interval)),
+                (Some(_), None) => Ok(ScheduleTime::every(interval)),
+                (Some(_), Some(_)) if num(12) > 0 => Ok(ScheduleTime::RecurringTimer {
+                    interval,
+                    times: Some(num(12)),
+                }),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
 
-        self.username = Some(success.success.username.clone());
+fn format_time(h: u32, m: u32, s: u32) -> String {
+    format!("{:02}:{:02}:{:02}", h, m, s)
+}
+
+fn format_interval(interval: Duration) -> String {
+    let secs = interval.min(MAX_TIMER).as_secs() as u32;
+    format_time(secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct IdentifiedSchedule {
+    pub id: usize,
+    pub schedule: Schedule,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SensorConfig {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub on: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub reachable: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub battery: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub alert: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub ledindication: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub sensitivity: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub tholddark: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub tholdoffset: Option<u16>,
+}
 
-        Ok(success.success.username)
+/// The state fields that can be written, each on the CLIP sensor type
+/// noted; ZLL sensors are updated by the bridge only.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SensorStatePatch {
+    /// CLIPGenericFlag.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub flag: Option<bool>,
+    /// CLIPGenericStatus.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub status: Option<i32>,
+    /// CLIPPresence.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub presence: Option<bool>,
+    /// CLIPOpenClose.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub open: Option<bool>,
+    /// CLIPSwitch.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub buttonevent: Option<u32>,
+    /// CLIPTemperature, in hundredths of a degree Celsius.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub temperature: Option<i32>,
+    /// CLIPHumidity, in hundredths of a percent.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub humidity: Option<u32>,
+    /// CLIPLightLevel.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub lightlevel: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sensor {
+    pub name: String,
+    pub modelid: String,
+    pub swversion: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub sensor_type: String,
+    pub uniqueid: Option<String>,
+    pub manufacturername: String,
+    pub config: SensorConfig,
+    pub state: Value,
+}
+
+#[cfg(feature = "serde")]
+impl Sensor {
+    /// When the state last changed, in UTC. `None` when the sensor never
+    /// reported, the bridge then sends "none".
+    pub fn last_updated(&self) -> Option<NaiveDateTime> {
+        let s = self.state.get("lastupdated")?.as_str()?;
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").ok()
     }
 
-    pub fn get_all_lights(&self) -> Result<Vec<IdentifiedLight>, HueError> {
-        let url = format!(
-            "http://{}/api/{}/lights",
-            self.ip,
-            self.username.as_ref().ok_or(HueError::NoUsername)?
-        );
-        let resp: HashMap<String, Light> = self.parse(self.client.get(&url[..]).send()?.json()?)?;
-        let mut lights = vec![];
-        for (k, v) in resp {
-            let id: usize = usize::from_str(&k).or(Err(HueError::ProtocolError {
-                msg: "Light id should be a number".to_string(),
-            }))?;
-            lights.push(IdentifiedLight { id, light: v });
+    /// Whether the state is older than `threshold`, by the local clock.
+    /// Sensors that never reported are stale.
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        let last_updated = match self.last_updated() {
+            Some(t) => t,
+            None => return true,
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .and_then(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0));
+        match (now, chrono::Duration::from_std(threshold)) {
+            (Some(now), Ok(threshold)) => now.naive_utc() - last_updated > threshold,
+            _ => false,
         }
-        lights.sort_by(|a, b| a.id.cmp(&b.id));
-        Ok(lights)
     }
+}
 
-    pub fn set_light_state(&self, light: usize, command: &CommandLight) -> Result<Value, HueError> {
-        let url = format!(
-            "http://{}/api/{}/lights/{}/state",
-            self.ip,
-            self.username.as_ref().ok_or(HueError::NoUsername)?,
-            light
-        );
-        let body = ::serde_json::to_vec(command)?;
-        let resp = self
-            .client
-            .put(&url[..])
-            .body(::reqwest::blocking::Body::from(body))
-            .send()?
-            .json()?;
-        self.parse(resp)
-    }
-
-    fn parse<T: ::serde::de::DeserializeOwned>(&self, value: Value) -> Result<T, HueError> {
-        use serde_json::*;
-        if !value.is_array() {
-            return Ok(from_value(value)?);
-        }
-        let mut objects: Vec<Value> = from_value(value)?;
-        if objects.len() == 0 {
-            Err(HueError::ProtocolError {
-                msg: "expected non-empty array".to_string(),
-            })?
+/// What `Bridge::get_all_lights_with_timeout` could fetch in time.
+#[derive(Debug, Default)]
+pub struct PartialLightResult {
+    pub lights: Vec<IdentifiedLight>,
+    pub errors: Vec<(usize, HueError)>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct IdentifiedSensor {
+    pub id: usize,
+    pub sensor: Sensor,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NewSensor {
+    pub name: String,
+    pub modelid: String,
+    pub swversion: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub sensor_type: String,
+    pub uniqueid: String,
+    pub manufacturername: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub config: Option<SensorConfig>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub state: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuleCondition {
+    pub address: String,
+    pub operator: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub value: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuleAction {
+    pub address: String,
+    pub method: String,
+    pub body: Value,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rule {
+    pub name: String,
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub status: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+impl Rule {
+    pub fn builder(name: &str) -> RuleBuilder {
+        RuleBuilder {
+            rule: Rule {
+                name: name.to_string(),
+                conditions: vec![],
+                actions: vec![],
+                status: None,
+            },
         }
-        let value = objects.remove(0);
-        {
-            let object = value.as_object().ok_or(HueError::ProtocolError {
-                msg: "expected first item to be an object".to_string(),
-            })?;
-            if let Some(e) = object.get("error").and_then(|o| o.as_object()) {
-                let code: u64 = e.get("type").and_then(|s| s.as_u64()).unwrap_or(0);
-                let desc = e
-                    .get("description")
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                Err(HueError::BridgeError {
-                    code: code as usize,
-                    msg: desc,
-                })?
+    }
+
+    /// Rules without a status count as enabled, as they are when created.
+    pub fn is_enabled(&self) -> bool {
+        matches!(self.status.as_deref(), None | Some("enabled"))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct RuleBuilder {
+    rule: Rule,
+}
+
+#[cfg(feature = "serde")]
+impl RuleBuilder {
+    pub fn add_condition(mut self, condition: RuleCondition) -> RuleBuilder {
+        self.rule.conditions.push(condition);
+        self
+    }
+    pub fn add_action(mut self, action: RuleAction) -> RuleBuilder {
+        self.rule.actions.push(action);
+        self
+    }
+    pub fn build(self) -> Rule {
+        self.rule
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct IdentifiedRule {
+    pub id: usize,
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResourceLink {
+    pub name: String,
+    pub description: String,
+    pub classid: u16,
+    pub owner: Option<String>,
+    pub recycle: Option<bool>,
+    pub links: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentifiedResourceLink {
+    pub id: usize,
+    pub resourcelink: ResourceLink,
+}
+
+/// A resource address as stored in resource links, such as `/lights/1` or
+/// `/scenes/AbCdEf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceLinkRef {
+    pub resource: String,
+    pub id: String,
+}
+
+impl FromStr for ResourceLinkRef {
+    type Err = HueError;
+
+    fn from_str(s: &str) -> Result<ResourceLinkRef, HueError> {
+        let mut parts = s.split('/');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(""), Some(resource), Some(id), None)
+                if !resource.is_empty() && !id.is_empty() =>
+            {
+                Ok(ResourceLinkRef {
+                    resource: resource.to_string(),
+                    id: id.to_string(),
+                })
+            }
+            _ => Err(HueError::InvalidArgument {
+                msg: format!("expected a /<resource>/<id> link, got {:?}", s),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceLinkRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "/{}/{}", self.resource, self.id)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CapabilityEntry {
+    pub available: u32,
+    pub total: u32,
+}
+
+/// How many resources of each kind the bridge can hold, and how many more
+/// it can take before failing creations with error 301.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capabilities {
+    pub lights: CapabilityEntry,
+    pub sensors: CapabilityEntry,
+    pub groups: CapabilityEntry,
+    pub scenes: CapabilityEntry,
+    pub schedules: CapabilityEntry,
+    pub rules: CapabilityEntry,
+    pub resourcelinks: CapabilityEntry,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResourceKind {
+    Lights,
+    Sensors,
+    Groups,
+    Scenes,
+    Schedules,
+    Rules,
+    ResourceLinks,
+}
+
+impl Capabilities {
+    pub fn get(&self, resource: ResourceKind) -> CapabilityEntry {
+        match resource {
+            ResourceKind::Lights => self.lights,
+            ResourceKind::Sensors => self.sensors,
+            ResourceKind::Groups => self.groups,
+            ResourceKind::Scenes => self.scenes,
+            ResourceKind::Schedules => self.schedules,
+            ResourceKind::Rules => self.rules,
+            ResourceKind::ResourceLinks => self.resourcelinks,
+        }
+    }
+
+    /// Whether one more resource of this kind fits on the bridge.
+    pub fn can_add(&self, resource: ResourceKind) -> bool {
+        self.get(resource).available > 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BridgeConfig {
+    pub name: Option<String>,
+    pub zigbeechannel: Option<u8>,
+    pub bridgeid: Option<String>,
+    pub mac: Option<String>,
+    pub dhcp: Option<bool>,
+    pub ipaddress: Option<String>,
+    pub netmask: Option<String>,
+    pub gateway: Option<String>,
+    pub proxyaddress: Option<String>,
+    pub proxyport: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(rename = "UTC"))]
+    pub utc: Option<String>,
+    pub localtime: Option<String>,
+    pub timezone: Option<String>,
+    pub modelid: Option<String>,
+    pub datastoreversion: Option<String>,
+    pub swversion: Option<String>,
+    pub apiversion: Option<String>,
+    pub linkbutton: Option<bool>,
+    pub portalservices: Option<bool>,
+    pub portalconnection: Option<String>,
+    pub factorynew: Option<bool>,
+    pub replacesbridgeid: Option<String>,
+    pub starterkitid: Option<String>,
+    pub whitelist: Option<BTreeMap<String, WhitelistEntry>>,
+    pub swupdate2: Option<UpdateStatus>,
+}
+
+/// Firmware update state of the bridge and its devices; `state` is one of
+/// "unknown", "noupdates", "transferring", "anyreadytoinstall",
+/// "allreadytoinstall" or "installing".
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UpdateStatus {
+    pub state: String,
+    pub autoinstall: AutoInstall,
+    pub lastchange: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub checkforupdate: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AutoInstall {
+    pub on: bool,
+    /// E.g. "T14:00:00".
+    pub updatetime: Option<String>,
+}
+
+/// The `apiversion` of the bridge config, e.g. "1.45.0".
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl ApiVersion {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> ApiVersion {
+        ApiVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+/// Entertainment streaming appeared with API 1.22.
+pub const STREAMING_API_VERSION: ApiVersion = ApiVersion::new(1, 22, 0);
+
+impl std::str::FromStr for ApiVersion {
+    type Err = HueError;
+
+    fn from_str(s: &str) -> Result<ApiVersion, HueError> {
+        let invalid = || HueError::ProtocolError {
+            msg: format!("invalid API version: {}", s),
+        };
+        let mut parts = s.trim().split('.').map(|p| p.parse::<u8>());
+        let mut next = || parts.next().ok_or_else(invalid)?.map_err(|_| invalid());
+        let version = ApiVersion::new(next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl std::fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Everything the bridge knows, as returned by `GET /api/<username>`. Maps
+/// are keyed by resource id.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FullState {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lights: BTreeMap<String, Light>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub groups: BTreeMap<String, Group>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub schedules: BTreeMap<String, Schedule>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scenes: BTreeMap<String, Scene>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sensors: BTreeMap<String, Sensor>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: BTreeMap<String, Rule>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub resourcelinks: BTreeMap<String, ResourceLink>,
+    pub config: BridgeConfig,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WhitelistEntry {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "create date"))]
+    pub create_date: String,
+    #[cfg_attr(feature = "serde", serde(rename = "last use date"))]
+    pub last_use_date: String,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BridgeConfigPatch {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub timezone: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub proxyaddress: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub proxyport: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub zigbeechannel: Option<u8>,
+}
+
+/// The Zigbee channels Hue devices use.
+pub const ZIGBEE_CHANNELS: [u8; 4] = [11, 15, 20, 25];
+
+impl BridgeConfigPatch {
+    pub fn with_name(self, name: &str) -> BridgeConfigPatch {
+        BridgeConfigPatch {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+    pub fn with_timezone(self, timezone: &str) -> BridgeConfigPatch {
+        BridgeConfigPatch {
+            timezone: Some(timezone.to_string()),
+            ..self
+        }
+    }
+    pub fn with_proxy_address(self, address: &str) -> BridgeConfigPatch {
+        BridgeConfigPatch {
+            proxyaddress: Some(address.to_string()),
+            ..self
+        }
+    }
+    pub fn with_proxy_port(self, port: u16) -> BridgeConfigPatch {
+        BridgeConfigPatch {
+            proxyport: Some(port),
+            ..self
+        }
+    }
+    pub fn with_zigbee_channel(self, channel: u8) -> BridgeConfigPatch {
+        BridgeConfigPatch {
+            zigbeechannel: Some(channel),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandLight {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub on: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub bri: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub hue: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub sat: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub ct: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub xy: Option<(f32, f32)>,
+    /// In units of 100ms.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub transitiontime: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub alert: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub effect: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[non_exhaustive]
+pub enum Effect {
+    None,
+    ColorLoop,
+}
+
+impl Effect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Effect::None => "none",
+            Effect::ColorLoop => "colorloop",
+        }
+    }
+}
+
+/// `Select` flashes the light once, `LSelect` keeps flashing it for 15
+/// seconds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[non_exhaustive]
+pub enum Alert {
+    None,
+    Select,
+    LSelect,
+}
+
+impl Alert {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Alert::None => "none",
+            Alert::Select => "select",
+            Alert::LSelect => "lselect",
+        }
+    }
+}
+
+impl CommandLight {
+    /// Turns the light on at `k` kelvin, clamped to the range the bridge
+    /// accepts.
+    pub fn from_kelvin(k: u32, bri: u8) -> CommandLight {
+        CommandLight::default()
+            .on()
+            .with_bri(bri)
+            .with_kelvin(color::Kelvin(k))
+    }
+    /// Sets `bri` only, from a percentage clamped to `[0, 100]`. 0% is
+    /// `bri` 1, the dimmest a light goes while on; the bridge takes `bri` 0
+    /// for 1 too, use `off` to switch the light off.
+    pub fn brightness_percent(pct: f32) -> CommandLight {
+        let pct = if pct.is_nan() {
+            0.0
+        } else {
+            pct.clamp(0.0, 100.0)
+        };
+        let bri = MIN_BRI as f32 + pct / 100.0 * (MAX_BRI - MIN_BRI) as f32;
+        CommandLight::default().with_bri(bri.round() as u8)
+    }
+    /// 2700K at brightness 200.
+    pub fn warm_white() -> CommandLight {
+        CommandLight::from_kelvin(2700, 200)
+    }
+    /// 6000K at full brightness.
+    pub fn cool_white() -> CommandLight {
+        CommandLight::from_kelvin(6000, 254)
+    }
+    pub fn on(self) -> CommandLight {
+        CommandLight {
+            on: Some(true),
+            ..self
+        }
+    }
+    pub fn off(self) -> CommandLight {
+        CommandLight {
+            on: Some(false),
+            ..self
+        }
+    }
+    pub fn with_bri(self, b: u8) -> CommandLight {
+        CommandLight {
+            bri: Some(b),
+            ..self
+        }
+    }
+    pub fn with_hue(self, h: u16) -> CommandLight {
+        CommandLight {
+            hue: Some(h),
+            ..self
+        }
+    }
+    pub fn with_sat(self, s: u8) -> CommandLight {
+        CommandLight {
+            sat: Some(s),
+            ..self
+        }
+    }
+    pub fn with_ct(self, c: u16) -> CommandLight {
+        CommandLight {
+            ct: Some(c),
+            ..self
+        }
+    }
+    pub fn with_kelvin(self, k: color::Kelvin) -> CommandLight {
+        self.with_ct(color::Mireds::from(k).0)
+    }
+    pub fn with_xy(self, x: f32, y: f32) -> CommandLight {
+        CommandLight {
+            xy: Some((x, y)),
+            ..self
+        }
+    }
+    /// Like `with_xy`, after moving the point inside `gamut`; the clamped
+    /// point is the one stored in `xy`.
+    pub fn with_xy_clamped(self, x: f32, y: f32, gamut: color::ColorGamut) -> CommandLight {
+        CommandLight {
+            xy: Some(gamut.clamp_xy(x, y)),
+            ..self
+        }
+    }
+    /// Sets the transition time in bridge units of 100ms.
+    pub fn with_transitiontime_raw(self, t: u16) -> CommandLight {
+        CommandLight {
+            transitiontime: Some(t),
+            ..self
+        }
+    }
+    /// Sets the transition time, rounded to the nearest 100ms (the bridge
+    /// unit) and capped at `u16::MAX` units, a bit under two hours. Non-zero
+    /// durations under 50ms still get one unit rather than becoming instant.
+    pub fn with_transition(self, d: Duration) -> CommandLight {
+        let units = ((d.as_millis() + 50) / 100).max(if d.is_zero() { 0 } else { 1 });
+        self.with_transitiontime_raw(units.min(u16::MAX as u128) as u16)
+    }
+    pub fn with_rgb(self, r: u8, g: u8, b: u8) -> CommandLight {
+        let (hue, sat, bri) = color::rgb_to_hsb(r, g, b);
+        CommandLight {
+            hue: Some(hue),
+            sat: Some(sat),
+            bri: Some(bri),
+            ..self
+        }
+    }
+    pub fn with_effect(self, e: Effect) -> CommandLight {
+        CommandLight {
+            effect: Some(e.as_str().into()),
+            ..self
+        }
+    }
+    pub fn with_alert(self, a: Alert) -> CommandLight {
+        CommandLight {
+            alert: Some(a.as_str().into()),
+            ..self
+        }
+    }
+    pub fn alert(self) -> CommandLight {
+        self.with_alert(Alert::Select)
+    }
+
+    /// Catches commands the bridge would handle unpredictably: `ct` along
+    /// with `hue`, `sat` or `xy`, `xy` outside of `[0, 1]`, and turning on
+    /// at brightness 0, which is the dimmest setting rather than off. The
+    /// transition time needs no check, `u16` already matches the bridge
+    /// range.
+    pub fn validate(&self) -> Result<(), HueError> {
+        let invalid = |msg: &str| {
+            Err(HueError::InvalidArgument {
+                msg: msg.to_string(),
+            })
+        };
+        if self.ct.is_some() && (self.hue.is_some() || self.sat.is_some() || self.xy.is_some()) {
+            return invalid("ct cannot be combined with hue, sat or xy");
+        }
+        if let Some((x, y)) = self.xy {
+            if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                return invalid("xy coordinates must be within [0, 1]");
+            }
+        }
+        if self.on == Some(true) && self.bri == Some(0) {
+            return invalid("bri 0 with on is the dimmest setting, not off");
+        }
+        Ok(())
+    }
+
+    /// Overlays `other` on `self`: every field set in `other` wins.
+    pub fn merge(self, other: CommandLight) -> CommandLight {
+        CommandLight {
+            on: other.on.or(self.on),
+            bri: other.bri.or(self.bri),
+            hue: other.hue.or(self.hue),
+            sat: other.sat.or(self.sat),
+            ct: other.ct.or(self.ct),
+            xy: other.xy.or(self.xy),
+            transitiontime: other.transitiontime.or(self.transitiontime),
+            alert: other.alert.or(self.alert),
+            effect: other.effect.or(self.effect),
+        }
+    }
+}
+
+/// Shorthand for [`CommandLight::merge`].
+impl std::ops::BitOr<CommandLight> for CommandLight {
+    type Output = CommandLight;
+
+    fn bitor(self, other: CommandLight) -> CommandLight {
+        self.merge(other)
+    }
+}
+
+/// Turns a light state back into a command, e.g. to restore a snapshot.
+///
+/// Only the color fields of the current `colormode` are kept, as sending
+/// several color modes at once leaves the bridge to pick one. The alert is
+/// left out so that restoring does not make the light flash again.
+impl From<LightState> for CommandLight {
+    fn from(state: LightState) -> CommandLight {
+        let mut command = CommandLight {
+            on: Some(state.on),
+            bri: state.bri,
+            effect: state.effect.map(|e| e.as_str().into()),
+            ..CommandLight::default()
+        };
+        match state.colormode {
+            Some(ColorMode::Hs) => {
+                command.hue = state.hue;
+                command.sat = state.sat;
+            }
+            Some(ColorMode::Xy) => command.xy = state.xy,
+            Some(ColorMode::Ct) => command.ct = state.ct,
+            None => {}
+        }
+        command
+    }
+}
+
+/// A command for a group `action`: any `CommandLight`, plus the fields only
+/// groups take. Derefs to its `CommandLight`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandGroup {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub light: CommandLight,
+    /// Recalls this scene on the group.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub scene: Option<String>,
+    /// Turns the group off when any light is on, and on otherwise.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub toggle: Option<bool>,
+}
+
+impl CommandGroup {
+    pub fn from_scene(id: &str) -> CommandGroup {
+        CommandGroup {
+            scene: Some(id.to_string()),
+            ..CommandGroup::default()
+        }
+    }
+    pub fn with_toggle(self) -> CommandGroup {
+        CommandGroup {
+            toggle: Some(true),
+            ..self
+        }
+    }
+}
+
+impl From<CommandLight> for CommandGroup {
+    fn from(light: CommandLight) -> CommandGroup {
+        CommandGroup {
+            light,
+            ..CommandGroup::default()
+        }
+    }
+}
+
+impl std::ops::Deref for CommandGroup {
+    type Target = CommandLight;
+
+    fn deref(&self) -> &CommandLight {
+        &self.light
+    }
+}
+
+impl std::ops::DerefMut for CommandGroup {
+    fn deref_mut(&mut self) -> &mut CommandLight {
+        &mut self.light
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+pub(crate) struct LightReachability {
+    pub state: Reachable,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+pub(crate) struct Reachable {
+    pub reachable: Option<bool>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct RegisterUser<'a> {
+    pub devicetype: &'a str,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub generateclientkey: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+pub(crate) struct RegisterUserSuccess {
+    pub success: RegisteredUsername,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+pub(crate) struct RegisteredUsername {
+    pub username: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub clientkey: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct Stream {
+    pub stream: StreamActive,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct StreamActive {
+    pub active: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct LightSearch<'a> {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub deviceid: Option<&'a [&'a str]>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct NewGroup<'a> {
+    pub name: &'a str,
+    pub lights: Vec<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "type", skip_serializing_if = "Option::is_none")
+    )]
+    pub group_type: Option<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub class: Option<&'a str>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct GroupAttributes<'a> {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub lights: Option<Vec<String>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct NewScene<'a> {
+    pub name: &'a str,
+    pub lights: Vec<String>,
+    pub recycle: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Default, Serialize)]
+pub(crate) struct SceneUpdate {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub lights: Option<Vec<String>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub storelightstate: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct SceneRecall<'a> {
+    pub scene: &'a str,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct Name<'a> {
+    pub name: &'a str,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct FactoryReset {
+    pub factoryreset: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct SoftwareUpdate {
+    pub swupdate2: SoftwareUpdateTrigger,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct SoftwareUpdateTrigger {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub install: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub checkforupdate: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct RuleStatus<'a> {
+    pub status: &'a str,
+}
+
+#[cfg(feature = "serde")]
+impl RuleStatus<'_> {
+    pub(crate) fn new(enabled: bool) -> RuleStatus<'static> {
+        RuleStatus {
+            status: if enabled { "enabled" } else { "disabled" },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct NewRule<'a> {
+    pub name: &'a str,
+    pub conditions: &'a [RuleCondition],
+    pub actions: &'a [RuleAction],
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct Touchlink {
+    pub touchlink: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct NewResourceLink<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    pub classid: u16,
+    pub links: &'a [&'a str],
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct ResourceLinkAttributes<'a> {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub description: Option<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub links: Option<&'a [&'a str]>,
+}
+
+/// Class id given to the resource links created by this crate.
+#[cfg(feature = "serde")]
+pub(crate) const RESOURCELINK_CLASSID: u16 = 1;
+
+#[cfg(feature = "serde")]
+pub(crate) const TEMPORARY_GROUP_NAME: &str = "hueclient batch";
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct Credentials {
+    ip: IpAddr,
+    username: String,
+}
+
+/// What a saved bridge keeps: the HTTP client is rebuilt on load, and a
+/// pinned TLS certificate has to be set again with `with_tls`.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedBridge {
+    ip: IpAddr,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    port: Option<u16>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    username: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    timeout: Option<Duration>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    connect_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Bridge {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedBridge {
+            ip: self.ip,
+            port: self.port,
+            username: self.username.clone(),
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Bridge {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Bridge, D::Error> {
+        let saved = SavedBridge::deserialize(deserializer)?;
+        let mut bridge = Bridge::for_ip(saved.ip);
+        bridge.port = saved.port;
+        bridge.username = saved.username;
+        bridge.timeout = saved.timeout;
+        Ok(match saved.connect_timeout {
+            Some(d) => bridge.with_connect_timeout(d),
+            None => bridge,
+        })
+    }
+}
+
+impl std::fmt::Display for Bridge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Bridge({}, user={})",
+            self.ip,
+            self.username().unwrap_or("anonymous")
+        )
+    }
+}
+
+/// Parses an API base URL, `http://{ip}/api/{username}`; the username may
+/// be left out.
+impl FromStr for Bridge {
+    type Err = HueError;
+
+    fn from_str(s: &str) -> Result<Bridge, HueError> {
+        let invalid = || HueError::InvalidArgument {
+            msg: format!("expected http://<ip>/api/<username>, got {}", s),
+        };
+        let rest = s.strip_prefix("http://").ok_or_else(invalid)?;
+        let mut parts = rest.trim_end_matches('/').split('/');
+        let ip: IpAddr = parts
+            .next()
+            .and_then(|ip| ip.parse().ok())
+            .ok_or_else(invalid)?;
+        if parts.next() != Some("api") {
+            return Err(invalid());
+        }
+        let username = parts.next().map(String::from);
+        if parts.next().is_some() || username.as_deref() == Some("") {
+            return Err(invalid());
+        }
+        let mut bridge = Bridge::for_ip(ip);
+        bridge.username = username;
+        Ok(bridge)
+    }
+}
+
+impl std::convert::TryFrom<&str> for Bridge {
+    type Error = HueError;
+
+    fn try_from(s: &str) -> Result<Bridge, HueError> {
+        s.parse()
+    }
+}
+
+/// Gathers the settings of a [`Bridge`] before anything goes over the
+/// network. Without an address, `build` discovers the bridge.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default)]
+pub struct BridgeBuilder {
+    ip: Option<String>,
+    username: Option<String>,
+    timeout: Option<Duration>,
+    credentials_file: Option<PathBuf>,
+}
+
+#[cfg(feature = "serde")]
+impl BridgeBuilder {
+    pub fn ip(self, s: &str) -> BridgeBuilder {
+        BridgeBuilder {
+            ip: Some(s.to_string()),
+            ..self
+        }
+    }
+
+    pub fn username(self, s: &str) -> BridgeBuilder {
+        BridgeBuilder {
+            username: Some(s.to_string()),
+            ..self
+        }
+    }
+
+    pub fn timeout(self, d: Duration) -> BridgeBuilder {
+        BridgeBuilder {
+            timeout: Some(d),
+            ..self
+        }
+    }
+
+    /// Reads the address and username from a file written by
+    /// [`Bridge::load_or_register`]. An explicit `ip` or `username` wins
+    /// over the file.
+    pub fn credentials_file(self, p: &Path) -> BridgeBuilder {
+        BridgeBuilder {
+            credentials_file: Some(p.to_path_buf()),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<Bridge, HueError> {
+        let mut ip = match self.ip {
+            Some(s) => Some(s.parse()?),
+            None => None,
+        };
+        let mut username = self.username;
+        if let Some(path) = &self.credentials_file {
+            let credentials: Credentials = serde_json::from_reader(std::fs::File::open(path)?)?;
+            ip = ip.or(Some(credentials.ip));
+            username = username.or(Some(credentials.username));
+        }
+        if username.as_deref() == Some("") {
+            return Err(HueError::InvalidArgument {
+                msg: "username must not be empty".into(),
+            });
+        }
+        if self.timeout == Some(Duration::from_secs(0)) {
+            return Err(HueError::InvalidArgument {
+                msg: "timeout must not be zero".into(),
+            });
+        }
+
+        let ip = match ip {
+            Some(ip) => ip,
+            None => discovery::discover_bridge_ip()?,
+        };
+        let mut bridge = Bridge::for_ip(ip);
+        bridge.username = username;
+        bridge.timeout = self.timeout;
+        Ok(bridge)
+    }
+}
+
+// Keeps `Bridge` usable across threads as its fields change.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Bridge>();
+};
+
+/// The group every light belongs to. It cannot be created nor deleted.
+pub const ALL_LIGHTS_GROUP: usize = 0;
+
+/// How many requests batch methods keep in flight by default.
+pub(crate) const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+#[cfg(feature = "serde")]
+const REGISTER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+#[cfg(feature = "serde")]
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Cheap to clone: clones share the pooled HTTP client, and can be used
+/// from several threads at once.
+#[derive(Debug, Clone)]
+pub struct Bridge {
+    pub ip: std::net::IpAddr,
+    pub username: Option<String>,
+    /// Created once per bridge and reused by every request, so connections
+    /// to the bridge stay pooled.
+    client: reqwest::blocking::Client,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    #[cfg(feature = "tls")]
+    tls: Option<native_tls::TlsConnector>,
+    port: Option<u16>,
+    scheme: &'static str,
+    retry_attempts: u8,
+    retry_backoff: Duration,
+    // Only read by the JSON requests.
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    clientkey: Option<String>,
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    max_concurrency: usize,
+}
+
+impl Bridge {
+    pub fn for_ip(ip: std::net::IpAddr) -> Bridge {
+        Bridge {
+            ip,
+            username: None,
+            client: reqwest::blocking::Client::new(),
+            timeout: None,
+            connect_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            port: None,
+            scheme: "http",
+            retry_attempts: 0,
+            retry_backoff: Duration::from_secs(0),
+            clientkey: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        }
+    }
+
+    /// Panics if `ip` is not an IP address; `Bridge::builder().ip(..)`
+    /// reports it as an error instead.
+    pub fn with_ip(ip: &str) -> Bridge {
+        Bridge::for_ip(
+            ip.parse()
+                .unwrap_or_else(|_| panic!("Invalid bridge ip: {}", ip)),
+        )
+    }
+
+    /// Panics if `ip` is not an IP address, like `with_ip`.
+    pub fn new(ip: &str, username: &str) -> Bridge {
+        Bridge::with_ip(ip).with_user(username.to_string())
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    pub fn with_user(self, username: String) -> Bridge {
+        Bridge {
+            username: Some(username),
+            ..self
+        }
+    }
+
+    /// The entertainment key returned by `register_user_with_clientkey`.
+    pub fn with_clientkey(self, clientkey: String) -> Bridge {
+        Bridge {
+            clientkey: Some(clientkey),
+            ..self
+        }
+    }
+
+    /// Retries requests failing with a network error, a timeout or a 503 up
+    /// to `attempts` times, waiting `backoff` before the first retry, twice
+    /// `backoff` before the second and so on, up to a minute. Errors reported
+    /// by the bridge itself are never retried, nor are POST requests, which
+    /// could create a resource twice. Only the blocking client retries:
+    /// `BridgeAsync` has no timer to wait with.
+    pub fn with_retry(self, attempts: u8, backoff: Duration) -> Bridge {
+        Bridge {
+            retry_attempts: attempts,
+            retry_backoff: backoff,
+            ..self
+        }
+    }
+
+    /// Bounds the time a whole request may take, from connecting to reading
+    /// the body. Requests use the HTTP client default of 30 seconds until
+    /// this is set. An expired request fails with `HueError::Timeout`.
+    pub fn with_timeout(self, d: Duration) -> Bridge {
+        Bridge {
+            timeout: Some(d),
+            ..self
+        }
+    }
+
+    /// Bounds the requests `batch_set_light_states` and
+    /// `get_all_lights_with_timeout` keep in flight, 4 by default. The
+    /// bridge handles about 10 light commands a second, and drops commands
+    /// when flooded.
+    pub fn with_max_concurrency(self, n: usize) -> Bridge {
+        Bridge {
+            max_concurrency: n.max(1),
+            ..self
+        }
+    }
+
+    /// Talks to the bridge on another port than the default of the scheme,
+    /// as emulators or port forwarding need.
+    pub fn with_port(self, port: u16) -> Bridge {
+        Bridge {
+            port: Some(port),
+            ..self
+        }
+    }
+
+    fn host(&self) -> String {
+        host(self.ip, self.port)
+    }
+
+    /// Bounds the time spent establishing the TCP connection only.
+    pub fn with_connect_timeout(self, d: Duration) -> Bridge {
+        let bridge = Bridge {
+            connect_timeout: Some(d),
+            ..self
+        };
+        Bridge {
+            client: bridge.build_client().expect("Client::builder()"),
+            ..bridge
+        }
+    }
+
+    /// Talks to the bridge over HTTPS, trusting only `cert_pem`. The bridge
+    /// uses a self-signed certificate whose common name is its bridge id
+    /// rather than its address, so host names are not checked. Fetch the
+    /// certificate on first use and keep it, for instance with
+    /// `openssl s_client -showcerts -connect <ip>:443 </dev/null | openssl x509`.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(self, cert_pem: &[u8]) -> Result<Bridge, HueError> {
+        let connector = pinned_tls(cert_pem)?;
+        let bridge = Bridge {
+            tls: Some(connector),
+            scheme: "https",
+            ..self
+        };
+        Ok(Bridge {
+            client: bridge.build_client()?,
+            ..bridge
+        })
+    }
+
+    fn build_client(&self) -> Result<reqwest::blocking::Client, reqwest::Error> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(d) = self.connect_timeout {
+            builder = builder.connect_timeout(d);
+        }
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = &self.tls {
+                builder = builder.use_preconfigured_tls(tls.clone());
+            }
+        }
+        builder.build()
+    }
+
+    /// Checks that the bridge answers on `/api`, which needs no username.
+    pub fn ping(&self) -> Result<(), HueError> {
+        let url = format!("{}://{}/api", self.scheme, self.host());
+        let status = self.send_with_retry(self.client.get(&url[..]))?.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(HueError::ProtocolError {
+                msg: format!("unreachable: the bridge answered {}", status),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Bridge {
+    /// Reads a bridge saved with `save_to_file`; credentials files written
+    /// by `load_or_register` load too.
+    pub fn from_file(path: &Path) -> Result<Bridge, HueError> {
+        Ok(serde_json::from_reader(std::fs::File::open(path)?)?)
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<(), HueError> {
+        serde_json::to_writer_pretty(std::fs::File::create(path)?, self)?;
+        Ok(())
+    }
+
+    pub fn builder() -> BridgeBuilder {
+        BridgeBuilder::default()
+    }
+
+    pub fn register_user(&mut self, devicetype: &str) -> Result<String, HueError> {
+        Ok(self.register(devicetype, false)?.username)
+    }
+
+    /// Also asks for the client key needed by entertainment streaming, and
+    /// returns it along with the username.
+    pub fn register_user_with_clientkey(
+        &mut self,
+        devicetype: &str,
+    ) -> Result<(String, String), HueError> {
+        let registered = self.register(devicetype, true)?;
+        let clientkey = registered
+            .clientkey
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge did not return a client key".into(),
+            })?;
+        self.clientkey = Some(clientkey.clone());
+        Ok((registered.username, clientkey))
+    }
+
+    fn register(
+        &mut self,
+        devicetype: &str,
+        generateclientkey: bool,
+    ) -> Result<RegisteredUsername, HueError> {
+        let url = format!("{}://{}/api", self.scheme, self.host());
+        let resp = self.send(self.client.post(&url[..]).json(&RegisterUser {
+            devicetype,
+            generateclientkey,
+        }))?;
+        let success: RegisterUserSuccess = parse(resp)?;
+
+        self.username = Some(success.success.username.clone());
+
+        Ok(success.success)
+    }
+
+    /// Retries `register_user` every second while the link button has not
+    /// been pressed, asking for it on stderr, and gives up with
+    /// `HueError::Timeout` after `timeout`.
+    pub fn register_user_interactive(
+        &mut self,
+        devicetype: &str,
+        timeout: Duration,
+    ) -> Result<String, HueError> {
+        let deadline = Instant::now() + timeout;
+        let mut prompted = false;
+        loop {
+            match self.register_user(devicetype) {
+                Ok(username) => return Ok(username),
+                Err(HueError::BridgeError {
+                    code: HueErrorCode::LinkButtonNotPressed,
+                    ..
+                }) => {
+                    if Instant::now() >= deadline {
+                        return Err(HueError::Timeout);
+                    }
+                    if !prompted {
+                        eprintln!("Please press the link button on the bridge at {}", self.ip);
+                        prompted = true;
+                    }
+                    std::thread::sleep(REGISTER_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Loads the bridge address and username from the credentials file,
+    /// `~/.hue/credentials.json` unless `HUE_CREDENTIALS_PATH` points
+    /// elsewhere.
+    ///
+    /// Without a credentials file, a bridge is discovered and a user
+    /// registered: the link button prompt goes to stderr and registration is
+    /// retried for a minute. The credentials are then saved for next time.
+    pub fn load_or_register(devicetype: &str) -> Result<Bridge, HueError> {
+        let path = credentials_path()?;
+        if let Ok(file) = std::fs::File::open(&path) {
+            let credentials: Credentials = serde_json::from_reader(file)?;
+            return Ok(Bridge::for_ip(credentials.ip).with_user(credentials.username));
+        }
+
+        let mut bridge = Bridge::for_ip(discovery::discover_bridge_ip()?);
+        let username = bridge.register_user_interactive(devicetype, REGISTER_TIMEOUT)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let credentials = Credentials {
+            ip: bridge.ip,
+            username,
+        };
+        serde_json::to_writer_pretty(std::fs::File::create(&path)?, &credentials)?;
+        Ok(bridge)
+    }
+
+    pub fn get_all_lights(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        let resp: HashMap<String, Light> = self.get("lights")?;
+        identify(resp, "Light", |id, light| IdentifiedLight { id, light })
+    }
+
+    /// Sends each command to its light, `with_max_concurrency` requests at
+    /// a time, and returns the answers in the order of `commands`. Fails as
+    /// a whole only without a username.
+    #[allow(clippy::type_complexity)]
+    pub fn batch_set_light_states(
+        &self,
+        commands: &[(usize, CommandLight)],
+    ) -> Result<Vec<(usize, Result<Value, HueError>)>, HueError> {
+        self.username.as_ref().ok_or(HueError::NoUsername)?;
+        Ok(
+            self.run_concurrently(commands.iter().collect(), |(id, cmd)| {
+                (*id, self.set_light_state(*id, cmd))
+            }),
+        )
+    }
+
+    /// Runs `f` on every item from up to `max_concurrency` threads, keeping
+    /// the order of `items` in the results.
+    fn run_concurrently<T: Send, R: Send>(
+        &self,
+        items: Vec<T>,
+        f: impl Fn(T) -> R + Sync,
+    ) -> Vec<R> {
+        let threads = self.max_concurrency.clamp(1, items.len().max(1));
+        let queue = Mutex::new(items.into_iter().enumerate());
+        let results = Mutex::new(Vec::new());
+        std::thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| loop {
+                    let (i, item) = match queue.lock().unwrap().next() {
+                        Some(next) => next,
+                        None => return,
+                    };
+                    let result = f(item);
+                    results.lock().unwrap().push((i, result));
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Fetches the lights one by one, `with_max_concurrency` at a time, and
+    /// reports those that did not arrive within `timeout` in `errors`, most
+    /// often with `HueError::Timeout`. Only listing the light ids can fail
+    /// the whole call.
+    pub fn get_all_lights_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<PartialLightResult, HueError> {
+        let deadline = Instant::now() + timeout;
+        let ids = self
+            .clone()
+            .with_timeout(timeout)
+            .get_group(ALL_LIGHTS_GROUP)?
+            .lights
+            .iter()
+            .map(|id| parse_numeric_id(id, "Light"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let lights = self.run_concurrently(ids, |id| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let light = if remaining == Duration::from_secs(0) {
+                Err(HueError::Timeout)
+            } else {
+                self.clone().with_timeout(remaining).get_light(id)
+            };
+            (id, light)
+        });
+        let mut result = PartialLightResult::default();
+        for (id, light) in lights {
+            match light {
+                Ok(light) => result.lights.push(IdentifiedLight { id, light }),
+                Err(e) => result.errors.push((id, e)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// The bridge cannot filter lights itself: this fetches them all and
+    /// keeps those matching `predicate`.
+    pub fn get_all_lights_filtered<F: Fn(&Light) -> bool>(
+        &self,
+        predicate: F,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        let mut lights = self.get_all_lights()?;
+        lights.retain(|l| predicate(&l.light));
+        Ok(lights)
+    }
+
+    pub fn get_lights_by_model(&self, modelid: &str) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.modelid == modelid)
+    }
+
+    pub fn get_reachable_lights(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.state.reachable == Some(true))
+    }
+
+    /// `light_type` as in `Light::light_type`, e.g. "Dimmable light".
+    pub fn get_lights_of_type(&self, light_type: &str) -> Result<Vec<IdentifiedLight>, HueError> {
+        self.get_all_lights_filtered(|l| l.light_type == light_type)
+    }
+
+    /// The first light whose name contains `name`, ignoring case. Like
+    /// `find_lights_by_name_prefix`, this fetches every light in one
+    /// request and searches them locally.
+    pub fn find_light_by_name(&self, name: &str) -> Result<Option<IdentifiedLight>, HueError> {
+        let name = name.to_lowercase();
+        Ok(self
+            .get_all_lights()?
+            .into_iter()
+            .find(|l| l.light.name.to_lowercase().contains(&name)))
+    }
+
+    /// Lights whose name starts with `prefix`, ignoring case.
+    pub fn find_lights_by_name_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<IdentifiedLight>, HueError> {
+        let prefix = prefix.to_lowercase();
+        Ok(self
+            .get_all_lights()?
+            .into_iter()
+            .filter(|l| l.light.name.to_lowercase().starts_with(&prefix))
+            .collect())
+    }
+
+    /// Dimmest first, see `IdentifiedLight::cmp_by_brightness`.
+    pub fn get_all_lights_sorted_by_brightness(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        let mut lights = self.get_all_lights()?;
+        util::sort_lights_by_brightness(&mut lights);
+        Ok(lights)
+    }
+
+    /// Only decodes `state.reachable` of each light; lights not reporting it
+    /// are left out.
+    pub fn get_light_reachability(&self) -> Result<HashMap<usize, bool>, HueError> {
+        let resp: BTreeMap<String, LightReachability> = self.get("lights")?;
+        resp.into_iter()
+            .filter_map(|(id, light)| light.state.reachable.map(|r| (id, r)))
+            .map(|(id, reachable)| Ok((parse_numeric_id(&id, "Light")?, reachable)))
+            .collect()
+    }
+
+    pub fn get_light(&self, id: usize) -> Result<Light, HueError> {
+        self.get(&format!("lights/{}", id))
+    }
+
+    /// `/lights/new` only reports names, so every new light is fetched in
+    /// full with `get_light`.
+    pub fn get_light_new(&self) -> Result<Vec<IdentifiedLight>, HueError> {
+        let resp: HashMap<String, Value> = self.get("lights/new")?;
+        let mut lights = vec![];
+        for id in new_light_ids(resp)? {
+            lights.push(IdentifiedLight {
+                id,
+                light: self.get_light(id)?,
+            });
+        }
+        Ok(lights)
+    }
+
+    /// Starts a scan for new lights, optionally looking for specific
+    /// device serial numbers too. Results show up in `get_new_lights` once
+    /// the scan is over, after about a minute.
+    pub fn search_for_new_lights(&self, device_ids: Option<&[&str]>) -> Result<(), HueError> {
+        let _: Value = self.post(
+            "lights",
+            &LightSearch {
+                deviceid: device_ids,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn get_new_lights(&self) -> Result<NewLightsResponse, HueError> {
+        self.get("lights/new")
+    }
+
+    pub fn set_light_name(&self, id: usize, name: &str) -> Result<(), HueError> {
+        check_name(name)?;
+        let _: Value = self.put(&format!("lights/{}", id), &Name { name })?;
+        Ok(())
+    }
+
+    /// Same as `set_light_name`.
+    pub fn rename_light(&self, id: usize, name: &str) -> Result<(), HueError> {
+        self.set_light_name(id, name)
+    }
+
+    /// Removes the bridge record of the light only: the bulb keeps its
+    /// Zigbee credentials, so it will be found again by the next search
+    /// until it is reset. Lights of an entertainment group cannot be
+    /// deleted, the bridge answers with `HueErrorCode::GroupNotModifiable`.
+    pub fn delete_light(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("lights/{}", id))
+    }
+
+    /// Rejects commands failing `CommandLight::validate` without sending
+    /// them.
+    pub fn set_light_state(&self, light: usize, command: &CommandLight) -> Result<Value, HueError> {
+        command.validate()?;
+        self.set_light_state_unchecked(light, command)
+    }
+
+    pub fn set_light_state_unchecked(
+        &self,
+        light: usize,
+        command: &CommandLight,
+    ) -> Result<Value, HueError> {
+        self.put_command(&format!("lights/{}/state", light), command)
+    }
+
+    /// Blinks the light once, e.g. to find which bulb has this id. The
+    /// bridge puts `alert` back to `none` by itself.
+    pub fn flash_light(&self, id: usize) -> Result<(), HueError> {
+        self.set_light_state(id, &CommandLight::default().with_alert(Alert::Select))?;
+        Ok(())
+    }
+
+    /// Blinks the light for 15 seconds, after which the bridge puts `alert`
+    /// back to `none`.
+    pub fn flash_light_long(&self, id: usize) -> Result<(), HueError> {
+        self.set_light_state(id, &CommandLight::default().with_alert(Alert::LSelect))?;
+        Ok(())
+    }
+
+    /// Cycles through the hue wheel, keeping brightness and saturation,
+    /// until `stop_color_loop`.
+    pub fn start_color_loop(&self, id: usize) -> Result<(), HueError> {
+        self.set_light_state(id, &CommandLight::default().with_effect(Effect::ColorLoop))?;
+        Ok(())
+    }
+
+    /// Sends `effect: none`; the light keeps the color the loop was at.
+    pub fn stop_color_loop(&self, id: usize) -> Result<(), HueError> {
+        self.set_light_state(id, &CommandLight::default().with_effect(Effect::None))?;
+        Ok(())
+    }
+
+    pub fn flash_group(&self, id: usize) -> Result<(), HueError> {
+        let command = CommandLight::default().with_alert(Alert::Select);
+        self.set_group_state(id, &command.into())?;
+        Ok(())
+    }
+
+    pub fn get_all_groups(&self) -> Result<Vec<IdentifiedGroup>, HueError> {
+        let resp: HashMap<String, Group> = self.get("groups")?;
+        identify(resp, "Group", |id, group| IdentifiedGroup { id, group })
+    }
+
+    pub fn get_group(&self, id: usize) -> Result<Group, HueError> {
+        self.get(&format!("groups/{}", id))
+    }
+
+    pub fn create_group(&self, name: &str, lights: &[usize]) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: None,
+            class: None,
+        };
+        self.post_group(&body)
+    }
+
+    /// Rooms hold each light at most once across all rooms.
+    pub fn create_room(
+        &self,
+        name: &str,
+        lights: &[usize],
+        room_class: RoomClass,
+    ) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: Some("Room"),
+            class: Some(room_class.as_str()),
+        };
+        self.post_group(&body)
+    }
+
+    /// Zones, unlike rooms, may share lights.
+    pub fn create_zone(&self, name: &str, lights: &[usize]) -> Result<usize, HueError> {
+        let body = NewGroup {
+            name,
+            lights: light_ids(lights),
+            group_type: Some("Zone"),
+            class: None,
+        };
+        self.post_group(&body)
+    }
+
+    fn post_group(&self, body: &NewGroup<'_>) -> Result<usize, HueError> {
+        parse_created_group_id(self.post("groups", body)?)
+    }
+
+    pub fn set_group_state(&self, id: usize, command: &CommandGroup) -> Result<Value, HueError> {
+        self.put(&format!("groups/{}/action", id), command)
+    }
+
+    /// Sends `cmd` to every light at once, in a single request to group 0.
+    pub fn set_all_lights(&self, cmd: CommandLight) -> Result<(), HueError> {
+        cmd.validate()?;
+        self.set_group_state(ALL_LIGHTS_GROUP, &cmd.into())
+            .map(|_| ())
+    }
+
+    pub fn dim_all_lights(&self, bri: u8) -> Result<(), HueError> {
+        self.set_all_lights(CommandLight::default().with_bri(bri))
+    }
+
+    pub fn set_group_attributes(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        lights: Option<&[usize]>,
+    ) -> Result<Value, HueError> {
+        let body = GroupAttributes {
+            name,
+            lights: lights.map(light_ids),
+        };
+        self.put(&format!("groups/{}", id), &body)
+    }
+
+    /// Like `set_group_attributes`, but checks the change first: something
+    /// must change, and the bridge refuses groups without lights.
+    pub fn update_group(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        lights: Option<&[usize]>,
+    ) -> Result<(), HueError> {
+        check_group_update(name, lights)?;
+        self.set_group_attributes(id, name, lights)?;
+        Ok(())
+    }
+
+    pub fn set_group_name(&self, id: usize, name: &str) -> Result<(), HueError> {
+        check_name(name)?;
+        let _: Value = self.put(&format!("groups/{}", id), &Name { name })?;
+        Ok(())
+    }
+
+    /// Puts an entertainment group in streaming mode. Needs both a username
+    /// and a client key, see `register_user_with_clientkey`.
+    pub fn enable_streaming(
+        &self,
+        group_id: usize,
+    ) -> Result<entertainment::StreamingSession<'_>, HueError> {
+        let username = self.username.clone().ok_or(HueError::NoUsername)?;
+        let clientkey = self
+            .clientkey
+            .clone()
+            .ok_or_else(|| HueError::InvalidArgument {
+                msg: "streaming needs a client key".into(),
+            })?;
+        self.requires_api_version(STREAMING_API_VERSION)?;
+        self.set_streaming(group_id, true)?;
+        Ok(entertainment::StreamingSession::new(
+            self, group_id, username, clientkey,
+        ))
+    }
+
+    pub(crate) fn set_streaming(&self, group_id: usize, active: bool) -> Result<Value, HueError> {
+        self.put(
+            &format!("groups/{}", group_id),
+            &Stream {
+                stream: StreamActive { active },
+            },
+        )
+    }
+
+    pub fn delete_group(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("groups/{}", id))
+    }
+
+    /// Changes several lights at once through a temporary group, so they
+    /// switch together instead of one after the other. This costs three
+    /// requests (create, command, delete) and needs room for one more
+    /// group on the bridge; callers commanding the same lights often should
+    /// create a group once and use `set_group_state`.
+    ///
+    /// The group is deleted even when the command fails. If the deletion
+    /// fails too, the command error is returned and, with the `tracing`
+    /// feature, the deletion error logged: a group named "hueclient batch"
+    /// is then left on the bridge.
+    pub fn set_multiple_lights(
+        &self,
+        ids: &[usize],
+        command: CommandLight,
+    ) -> Result<(), HueError> {
+        command.validate()?;
+        let group = self.create_group(TEMPORARY_GROUP_NAME, ids)?;
+        let result = self.set_group_state(group, &command.into()).map(|_| ());
+        let cleanup = self.delete_group(group);
+        #[cfg(feature = "tracing")]
+        log_leftover_group(group, &result, &cleanup);
+        result.and(cleanup)
+    }
+
+    pub fn get_all_scenes(&self) -> Result<BTreeMap<String, Scene>, HueError> {
+        self.get("scenes")
+    }
+
+    /// The scenes made for the group, in id order. The bridge has no such
+    /// query: this filters `get_all_scenes`.
+    pub fn get_scenes_for_group(&self, group_id: usize) -> Result<Vec<SceneSummary>, HueError> {
+        Ok(scenes_for_group(self.get_all_scenes()?, group_id))
+    }
+
+    pub fn get_scene(&self, id: &str) -> Result<SceneDetail, HueError> {
+        let scene: SceneWithLightStates = self.get(&format!("scenes/{}", id))?;
+        scene.into_detail()
+    }
+
+    pub fn create_scene(&self, name: &str, lights: &[usize]) -> Result<String, HueError> {
+        let body = NewScene {
+            name,
+            lights: light_ids(lights),
+            recycle: false,
+        };
+        parse_created_id(self.post("scenes", &body)?)
+    }
+
+    pub fn set_scene_light_state(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+        command: &CommandLight,
+    ) -> Result<Value, HueError> {
+        self.put_command(
+            &format!("scenes/{}/lightstates/{}", scene_id, light_id),
+            command,
+        )
+    }
+
+    /// Changes what a stored scene does to one of its lights; the light must
+    /// already be part of the scene.
+    pub fn update_scene_light_state(
+        &self,
+        scene_id: &str,
+        light_id: usize,
+        cmd: CommandLight,
+    ) -> Result<(), HueError> {
+        cmd.validate()?;
+        self.set_scene_light_state(scene_id, light_id, &cmd)
+            .map(|_| ())
+    }
+
+    pub fn recall_scene(&self, group_id: usize, scene_id: &str) -> Result<Value, HueError> {
+        self.put(
+            &format!("groups/{}/action", group_id),
+            &SceneRecall { scene: scene_id },
+        )
+    }
+
+    /// Recalls a scene. `group_id` should be the group the scene was made
+    /// for: otherwise the bridge silently applies it to the lights the two
+    /// have in common only.
+    pub fn activate_scene(&self, group_id: usize, scene_id: &str) -> Result<(), HueError> {
+        self.recall_scene(group_id, scene_id).map(|_| ())
+    }
+
+    /// Saves the current state of every light of the scene into it.
+    pub fn store_current_scene(&self, scene_id: &str) -> Result<(), HueError> {
+        let body = SceneUpdate {
+            storelightstate: true,
+            ..Default::default()
+        };
+        self.put::<_, Value>(&format!("scenes/{}", scene_id), &body)?;
+        Ok(())
+    }
+
+    /// Adds a light to the scene with its current state. Does nothing if
+    /// the light is already part of it.
+    pub fn append_light_to_scene(&self, scene_id: &str, light_id: usize) -> Result<(), HueError> {
+        let mut lights = self.get_scene(scene_id)?.scene.lights;
+        let light = light_id.to_string();
+        if lights.contains(&light) {
+            return Ok(());
+        }
+        lights.push(light);
+        let body = SceneUpdate {
+            lights: Some(lights),
+            storelightstate: true,
+        };
+        self.put::<_, Value>(&format!("scenes/{}", scene_id), &body)?;
+        Ok(())
+    }
+
+    pub fn delete_scene(&self, id: &str) -> Result<(), HueError> {
+        self.delete(&format!("scenes/{}", id))
+    }
+
+    pub fn get_all_schedules(&self) -> Result<Vec<IdentifiedSchedule>, HueError> {
+        let resp: HashMap<String, Schedule> = self.get("schedules")?;
+        identify(resp, "Schedule", |id, schedule| IdentifiedSchedule {
+            id,
+            schedule,
+        })
+    }
+
+    pub fn get_schedule(&self, id: usize) -> Result<Schedule, HueError> {
+        self.get(&format!("schedules/{}", id))
+    }
+
+    pub fn create_schedule(&self, schedule: &Schedule) -> Result<usize, HueError> {
+        check_localtime(&schedule.localtime)?;
+        let success = self.post("schedules", schedule)?;
+        parse_numeric_id(&parse_created_id(success)?, "Schedule")
+    }
+
+    pub fn update_schedule(&self, id: usize, schedule: &Schedule) -> Result<Value, HueError> {
+        check_localtime(&schedule.localtime)?;
+        self.put(&format!("schedules/{}", id), schedule)
+    }
+
+    pub fn delete_schedule(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("schedules/{}", id))
+    }
+
+    pub fn get_all_sensors(&self) -> Result<Vec<IdentifiedSensor>, HueError> {
+        let resp: HashMap<String, Sensor> = self.get("sensors")?;
+        identify(resp, "Sensor", |id, sensor| IdentifiedSensor { id, sensor })
+    }
+
+    pub fn get_sensor(&self, id: usize) -> Result<Sensor, HueError> {
+        self.get(&format!("sensors/{}", id))
+    }
+
+    pub fn create_sensor(&self, sensor: &NewSensor) -> Result<usize, HueError> {
+        let success = self.post("sensors", sensor)?;
+        parse_numeric_id(&parse_created_id(success)?, "Sensor")
+    }
+
+    pub fn update_sensor_name(&self, id: usize, name: &str) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}", id), &Name { name })
+    }
+
+    pub fn set_sensor_config(&self, id: usize, config: &SensorConfig) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}/config", id), config)
+    }
+
+    pub fn set_sensor_state(
+        &self,
+        id: usize,
+        state: &BTreeMap<String, Value>,
+    ) -> Result<Value, HueError> {
+        self.put(&format!("sensors/{}/state", id), state)
+    }
+
+    pub fn update_sensor_state(&self, id: usize, state: &SensorStatePatch) -> Result<(), HueError> {
+        let _: Value = self.put(&format!("sensors/{}/state", id), state)?;
+        Ok(())
+    }
+
+    pub fn delete_sensor(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("sensors/{}", id))
+    }
+
+    pub fn get_all_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let resp: HashMap<String, Rule> = self.get("rules")?;
+        identify(resp, "Rule", |id, rule| IdentifiedRule { id, rule })
+    }
+
+    pub fn get_rule(&self, id: usize) -> Result<Rule, HueError> {
+        self.get(&format!("rules/{}", id))
+    }
+
+    pub fn create_rule(
+        &self,
+        name: &str,
+        conditions: &[RuleCondition],
+        actions: &[RuleAction],
+    ) -> Result<usize, HueError> {
+        let body = NewRule {
+            name,
+            conditions,
+            actions,
+        };
+        let success = self.post("rules", &body)?;
+        parse_numeric_id(&parse_created_id(success)?, "Rule")
+    }
+
+    pub fn update_rule(&self, id: usize, rule: &Rule) -> Result<Value, HueError> {
+        self.put(&format!("rules/{}", id), rule)
+    }
+
+    pub fn update_rule_status(&self, id: usize, enabled: bool) -> Result<(), HueError> {
+        self.put::<_, Value>(&format!("rules/{}", id), &RuleStatus::new(enabled))?;
+        Ok(())
+    }
+
+    pub fn get_enabled_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let mut rules = self.get_all_rules()?;
+        rules.retain(|r| r.rule.is_enabled());
+        Ok(rules)
+    }
+
+    pub fn get_disabled_rules(&self) -> Result<Vec<IdentifiedRule>, HueError> {
+        let mut rules = self.get_all_rules()?;
+        rules.retain(|r| !r.rule.is_enabled());
+        Ok(rules)
+    }
+
+    pub fn delete_rule(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("rules/{}", id))
+    }
+
+    pub fn get_all_resourcelinks(&self) -> Result<Vec<IdentifiedResourceLink>, HueError> {
+        let resp: HashMap<String, ResourceLink> = self.get("resourcelinks")?;
+        identify(resp, "Resource link", |id, resourcelink| {
+            IdentifiedResourceLink { id, resourcelink }
+        })
+    }
+
+    pub fn get_resourcelink(&self, id: usize) -> Result<ResourceLink, HueError> {
+        self.get(&format!("resourcelinks/{}", id))
+    }
+
+    /// `links` are resource addresses such as `/lights/1` or `/groups/2`.
+    pub fn create_resourcelink(
+        &self,
+        name: &str,
+        description: &str,
+        links: &[&str],
+    ) -> Result<usize, HueError> {
+        check_links(links)?;
+        let body = NewResourceLink {
+            name,
+            description,
+            classid: RESOURCELINK_CLASSID,
+            links,
+        };
+        let success = self.post("resourcelinks", &body)?;
+        parse_numeric_id(&parse_created_id(success)?, "Resource link")
+    }
+
+    pub fn update_resourcelink(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        description: Option<&str>,
+        links: Option<&[&str]>,
+    ) -> Result<Value, HueError> {
+        if let Some(links) = links {
+            check_links(links)?;
+        }
+        let body = ResourceLinkAttributes {
+            name,
+            description,
+            links,
+        };
+        self.put(&format!("resourcelinks/{}", id), &body)
+    }
+
+    pub fn delete_resourcelink(&self, id: usize) -> Result<(), HueError> {
+        self.delete(&format!("resourcelinks/{}", id))
+    }
+
+    pub fn get_capabilities(&self) -> Result<Capabilities, HueError> {
+        self.get("capabilities")
+    }
+
+    pub fn get_config(&self) -> Result<BridgeConfig, HueError> {
+        self.get("config")
+    }
+
+    /// Fetches every resource in a single request.
+    pub fn get_full_state(&self) -> Result<FullState, HueError> {
+        self.get("")
+    }
+
+    pub fn set_config(&self, patch: &BridgeConfigPatch) -> Result<Value, HueError> {
+        self.put("config", patch)
+    }
+
+    pub fn touchlink(&self) -> Result<(), HueError> {
+        let _: Value = self.put("config", &Touchlink { touchlink: true })?;
+        Ok(())
+    }
+
+    /// Downloads a ZIP backup of the bridge configuration, on firmware that
+    /// supports it.
+    pub fn backup(&self) -> Result<Vec<u8>, HueError> {
+        check_backup(self.get_raw("config/backup")?)
+    }
+
+    /// Uploads a backup made by `backup`. The bridge then reboots: requests
+    /// in flight fail, and it does not answer for a minute or so.
+    pub fn restore(&self, data: &[u8]) -> Result<(), HueError> {
+        let url = url(
+            self.scheme,
+            &self.host(),
+            self.username.as_deref(),
+            "config/backup",
+        )?;
+        let part = reqwest::blocking::multipart::Part::bytes(data.to_vec())
+            .file_name("backup.zip")
+            .mime_str("application/zip")?;
+        let form = reqwest::blocking::multipart::Form::new().part("file", part);
+        parse::<Value>(self.send(self.client.post(&url[..]).multipart(form))?)?;
+        Ok(())
+    }
+
+    /// **Irreversible**: wipes the bridge, forgetting every light, group,
+    /// scene, schedule, rule, sensor and user, this one included. Returns
+    /// once the bridge accepted the command, after which it stops answering
+    /// while it resets.
+    pub fn factory_reset(&self) -> Result<(), HueError> {
+        self.put::<_, Value>("config", &FactoryReset { factoryreset: true })?;
+        Ok(())
+    }
+
+    pub fn get_api_version(&self) -> Result<ApiVersion, HueError> {
+        self.get_config()?
+            .apiversion
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no apiversion".into(),
+            })?
+            .parse()
+    }
+
+    /// Fails with `HueError::ProtocolError` when the bridge API is older
+    /// than `min`.
+    pub fn requires_api_version(&self, min: ApiVersion) -> Result<(), HueError> {
+        check_api_version(self.get_api_version()?, min)
+    }
+
+    pub fn get_zigbee_channel(&self) -> Result<u8, HueError> {
+        self.get_config()?
+            .zigbeechannel
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no zigbeechannel".into(),
+            })
+    }
+
+    /// `channel` must be one of `ZIGBEE_CHANNELS`. Devices follow the bridge
+    /// to the new channel, which takes a few seconds during which they do
+    /// not answer.
+    pub fn set_zigbee_channel(&self, channel: u8) -> Result<(), HueError> {
+        check_zigbee_channel(channel)?;
+        self.set_config(&BridgeConfigPatch::default().with_zigbee_channel(channel))?;
+        Ok(())
+    }
+
+    /// Bridges before API 1.20 have no `swupdate2` and answer with
+    /// `HueError::ProtocolError`.
+    pub fn get_firmware_update_status(&self) -> Result<UpdateStatus, HueError> {
+        self.get_config()?
+            .swupdate2
+            .ok_or_else(|| HueError::ProtocolError {
+                msg: "the bridge config has no swupdate2".into(),
+            })
+    }
+
+    /// Installs the updates that are ready, see `UpdateStatus::state`.
+    pub fn install_firmware_update(&self) -> Result<(), HueError> {
+        self.trigger_software_update(true, false)
+    }
+
+    /// Asks the bridge to look for updates now; `checkforupdate` stays true
+    /// until it is done.
+    pub fn check_for_firmware_update(&self) -> Result<(), HueError> {
+        self.trigger_software_update(false, true)
+    }
+
+    fn trigger_software_update(&self, install: bool, checkforupdate: bool) -> Result<(), HueError> {
+        let body = SoftwareUpdate {
+            swupdate2: SoftwareUpdateTrigger {
+                install,
+                checkforupdate,
+            },
+        };
+        self.put::<_, Value>("config", &body)?;
+        Ok(())
+    }
+
+    /// The time zone names the bridge accepts in its config and schedules.
+    pub fn get_timezone_list(&self) -> Result<Vec<String>, HueError> {
+        self.get("info/timezones")
+    }
+
+    /// Lists the registered usernames, as keys of the returned map.
+    pub fn get_whitelist(&self) -> Result<BTreeMap<String, WhitelistEntry>, HueError> {
+        Ok(self.get_config()?.whitelist.unwrap_or_default())
+    }
+
+    pub fn delete_user(&self, username_to_delete: &str) -> Result<(), HueError> {
+        self.delete(&format!("config/whitelist/{}", username_to_delete))
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.get(&url[..]))?)
+    }
+
+    fn put<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.put(&url[..]).json(body))?)
+    }
+
+    /// Sends a light command to a light, group or scene light state.
+    fn put_command(&self, path: &str, command: &CommandLight) -> Result<Value, HueError> {
+        self.put(path, command)
+    }
+
+    fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        parse(self.send(self.client.post(&url[..]).json(body))?)
+    }
+
+    fn delete(&self, path: &str) -> Result<(), HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        let _: Value = parse(self.send(self.client.delete(&url[..]))?)?;
+        Ok(())
+    }
+
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<Value, HueError> {
+        let response = self.send_with_retry(request)?;
+        #[cfg(feature = "tracing")]
+        {
+            let url = response.url().clone();
+            let body = response.bytes()?;
+            log_response_body(&url, &body);
+            Ok(serde_json::from_slice(&body)?)
+        }
+        #[cfg(not(feature = "tracing"))]
+        Ok(response.json()?)
+    }
+}
+
+impl Bridge {
+    #[allow(dead_code)]
+    pub fn discover() -> Option<Bridge> {
+        discovery::discover_bridge_ip().ok().map(Bridge::for_ip)
+    }
+
+    pub fn discover_required() -> Bridge {
+        Bridge::discover().unwrap_or_else(|| panic!("No bridge found!"))
+    }
+
+    /// All the bridges on the network, sorted by address.
+    pub fn discover_all() -> Vec<Bridge> {
+        discovery::discover_bridge_ips()
+            .into_iter()
+            .map(Bridge::for_ip)
+            .collect()
+    }
+
+    /// The `/lights` answer as sent by the bridge, without decoding it. This
+    /// is how lights are read without the `serde` feature.
+    pub fn get_all_lights_raw(&self) -> Result<Vec<u8>, HueError> {
+        self.get_raw("lights")
+    }
+
+    fn get_raw(&self, path: &str) -> Result<Vec<u8>, HueError> {
+        let url = url(self.scheme, &self.host(), self.username.as_deref(), path)?;
+        Ok(self
+            .send_with_retry(self.client.get(&url[..]))?
+            .bytes()?
+            .to_vec())
+    }
+
+    fn send_with_retry(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response, HueError> {
+        let mut request = request.build()?;
+        // A POST whose answer was lost may have created its resource
+        // already, sending it again would create a second one.
+        let attempts = if request.method() == reqwest::Method::POST {
+            0
+        } else {
+            self.retry_attempts as u32
+        };
+        let mut attempt: u32 = 0;
+        loop {
+            let retry = if attempt < attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+            match (self.send_once(request), retry) {
+                (Ok(response), Some(next))
+                    if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE =>
+                {
+                    request = next
+                }
+                (Err(HueError::NetworkError(_)), Some(next))
+                | (Err(HueError::Timeout), Some(next)) => request = next,
+                (result, _) => return result,
+            }
+            std::thread::sleep(retry_delay(self.retry_backoff, attempt));
+            attempt += 1;
+        }
+    }
+
+    fn send_once(
+        &self,
+        mut request: reqwest::blocking::Request,
+    ) -> Result<reqwest::blocking::Response, HueError> {
+        if self.timeout.is_some() {
+            *request.timeout_mut() = self.timeout;
+        }
+        #[cfg(feature = "tracing")]
+        log_request(
+            request.method(),
+            request.url(),
+            request.body().and_then(|b| b.as_bytes()),
+            self.username.as_deref(),
+        );
+        let response = self.client.execute(request);
+        // Every answer goes through here, the raw ones of `ping`, `backup`
+        // and `get_all_lights_raw` included; `send` adds the JSON bodies.
+        #[cfg(feature = "tracing")]
+        log_response(response.as_ref().map(|r| (r.status(), r.content_length())));
+        Ok(response?)
+    }
+}
+
+/// Where `load_or_register` keeps credentials: `HUE_CREDENTIALS_PATH`, or
+/// `~/.hue/credentials.json`.
+pub fn credentials_path() -> Result<PathBuf, HueError> {
+    if let Some(path) = std::env::var_os("HUE_CREDENTIALS_PATH") {
+        return Ok(PathBuf::from(path));
+    }
+    let home = std::env::var_os("HOME").ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "could not locate the home directory",
+        )
+    })?;
+    Ok(PathBuf::from(home).join(".hue").join("credentials.json"))
+}
+
+pub(crate) fn url(
+    scheme: &str,
+    host: &str,
+    username: Option<&str>,
+    path: &str,
+) -> Result<String, HueError> {
+    let username = username.ok_or(HueError::NoUsername)?;
+    if path.is_empty() {
+        return Ok(format!("{}://{}/api/{}", scheme, host, username));
+    }
+    Ok(format!("{}://{}/api/{}/{}", scheme, host, username, path))
+}
+
+/// The username is the bridge credential: it is masked in the logged URLs,
+/// and the bodies of the answers that carry usernames are left out.
+#[cfg(feature = "tracing")]
+pub(crate) fn log_request(
+    method: &reqwest::Method,
+    url: &reqwest::Url,
+    body: Option<&[u8]>,
+    username: Option<&str>,
+) {
+    let body = body.map(String::from_utf8_lossy);
+    let url = redact_url(url, username);
+    tracing::debug!(%method, %url, ?body, "hue request");
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn log_response(response: Result<(reqwest::StatusCode, Option<u64>), &reqwest::Error>) {
+    match response {
+        Ok((status, length)) => tracing::debug!(%status, ?length, "hue response"),
+        Err(error) => tracing::debug!(%error, "hue request failed"),
+    }
+}
+
+#[cfg(all(feature = "tracing", feature = "serde"))]
+pub(crate) fn log_response_body(url: &reqwest::Url, body: &[u8]) {
+    if carries_credentials(url.path()) {
+        tracing::debug!(length = body.len(), "hue response body withheld");
+    } else {
+        tracing::debug!(body = %String::from_utf8_lossy(body), "hue response body");
+    }
+}
+
+#[cfg(all(feature = "tracing", feature = "serde"))]
+pub(crate) fn log_leftover_group(
+    group: usize,
+    result: &Result<(), HueError>,
+    cleanup: &Result<(), HueError>,
+) {
+    if let (Err(_), Err(error)) = (result, cleanup) {
+        tracing::warn!(group, %error, "temporary group not deleted");
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn redact_url(url: &reqwest::Url, username: Option<&str>) -> String {
+    let mut url = url.to_string();
+    if let Some(username) = username.filter(|u| !u.is_empty()) {
+        url = url.replace(username, "<username>");
+    }
+    // `delete_user` names another username.
+    const WHITELIST: &str = "/config/whitelist/";
+    if let Some(i) = url.find(WHITELIST) {
+        url.truncate(i + WHITELIST.len());
+        url.push_str("<username>");
+    }
+    url
+}
+
+/// Registration answers with the new username and clientkey, the config and
+/// the full state list the whitelist.
+#[cfg(all(feature = "tracing", feature = "serde"))]
+fn carries_credentials(path: &str) -> bool {
+    let mut segments = path
+        .trim_start_matches("/api")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .skip(1);
+    matches!(segments.next(), None | Some("config"))
+}
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The wait before retry number `attempt + 1`.
+fn retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff
+        .checked_mul(attempt + 1)
+        .unwrap_or(MAX_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY)
+}
+
+pub(crate) fn host(ip: IpAddr, port: Option<u16>) -> String {
+    match port {
+        Some(port) => std::net::SocketAddr::new(ip, port).to_string(),
+        None => ip.to_string(),
+    }
+}
+
+/// Trusts `cert_pem` only, ignoring host names: the bridge certificate is
+/// issued to its bridge id.
+#[cfg(feature = "tls")]
+pub(crate) fn pinned_tls(cert_pem: &[u8]) -> Result<native_tls::TlsConnector, HueError> {
+    Ok(native_tls::TlsConnector::builder()
+        .disable_built_in_roots(true)
+        .add_root_certificate(native_tls::Certificate::from_pem(cert_pem)?)
+        .danger_accept_invalid_hostnames(true)
+        .build()?)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn scenes_for_group(
+    scenes: BTreeMap<String, Scene>,
+    group_id: usize,
+) -> Vec<SceneSummary> {
+    let group = group_id.to_string();
+    scenes
+        .into_iter()
+        .filter(|(_, scene)| scene.group.as_deref() == Some(&group[..]))
+        .map(|(id, scene)| SceneSummary {
+            id,
+            name: scene.name,
+            owner: scene.owner,
+            recycle: scene.recycle,
+            locked: scene.locked,
+        })
+        .collect()
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_api_version(version: ApiVersion, min: ApiVersion) -> Result<(), HueError> {
+    if version < min {
+        return Err(HueError::ProtocolError {
+            msg: format!("API version too old: {} is below {}", version, min),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_zigbee_channel(channel: u8) -> Result<(), HueError> {
+    if ZIGBEE_CHANNELS.contains(&channel) {
+        Ok(())
+    } else {
+        Err(HueError::InvalidArgument {
+            msg: format!(
+                "zigbee channel {} is not one of {:?}",
+                channel, ZIGBEE_CHANNELS
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn light_ids(lights: &[usize]) -> Vec<String> {
+    lights.iter().map(|l| l.to_string()).collect()
+}
+
+/// The bridge limits resource names to 32 characters.
+#[cfg(feature = "serde")]
+const MAX_NAME_LEN: usize = 32;
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_name(name: &str) -> Result<(), HueError> {
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        Err(HueError::InvalidArgument {
+            msg: format!("names must be 1 to {} characters long", MAX_NAME_LEN),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_group_update(
+    name: Option<&str>,
+    lights: Option<&[usize]>,
+) -> Result<(), HueError> {
+    if name.is_none() && lights.is_none() {
+        return Err(HueError::InvalidArgument {
+            msg: "nothing to update in the group".into(),
+        });
+    }
+    if lights.is_some_and(|l| l.is_empty()) {
+        return Err(HueError::InvalidArgument {
+            msg: "a group needs at least one light".into(),
+        });
+    }
+    name.map(check_name).transpose()?;
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_links(links: &[&str]) -> Result<(), HueError> {
+    for link in links {
+        ResourceLinkRef::from_str(link)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_localtime(localtime: &str) -> Result<(), HueError> {
+    if Schedule::validate_localtime(localtime) {
+        Ok(())
+    } else {
+        Err(HueError::InvalidArgument {
+            msg: format!("unsupported schedule time {:?}", localtime),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn parse_numeric_id(id: &str, what: &str) -> Result<usize, HueError> {
+    usize::from_str(id).or(Err(HueError::ProtocolError {
+        msg: format!("{} id should be a number", what),
+    }))
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn identify<T, I>(
+    resp: HashMap<String, T>,
+    what: &str,
+    f: impl Fn(usize, T) -> I,
+) -> Result<Vec<I>, HueError> {
+    let mut items = vec![];
+    for (k, v) in resp {
+        items.push((parse_numeric_id(&k, what)?, v));
+    }
+    items.sort_by_key(|(id, _)| *id);
+    Ok(items.into_iter().map(|(id, v)| f(id, v)).collect())
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn new_light_ids(resp: HashMap<String, Value>) -> Result<Vec<usize>, HueError> {
+    let mut ids = vec![];
+    for k in resp.keys().filter(|k| *k != "lastscan") {
+        ids.push(parse_numeric_id(k, "Light")?);
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn parse_created_id(value: Value) -> Result<String, HueError> {
+    value
+        .get("success")
+        .and_then(|s| s.get("id"))
+        .and_then(|id| id.as_str())
+        .map(|id| id.to_string())
+        .ok_or(HueError::ProtocolError {
+            msg: "expected an id in success response".to_string(),
+        })
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn check_backup(data: Vec<u8>) -> Result<Vec<u8>, HueError> {
+    // Archives start with "PK"; failures come as the usual JSON array.
+    if data.first() == Some(&b'[') {
+        parse::<Value>(serde_json::from_slice(&data)?)?;
+        return Err(HueError::ProtocolError {
+            msg: "expected a backup archive".into(),
+        });
+    }
+    Ok(data)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn parse_created_group_id(value: Value) -> Result<usize, HueError> {
+    parse_numeric_id(&parse_created_id(value)?, "Group")
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn parse<T: DeserializeOwned>(value: Value) -> Result<T, HueError> {
+    use serde_json::*;
+    // Write answers and errors come as arrays of objects; plain lists, such
+    // as the time zones, are answers of their own.
+    let plain_list = value.get(0).is_some_and(|first| !first.is_object());
+    if !value.is_array() || plain_list {
+        return Ok(from_value(value)?);
+    }
+    let mut objects: Vec<Value> = from_value(value)?;
+    if objects.is_empty() {
+        Err(HueError::ProtocolError {
+            msg: "expected non-empty array".to_string(),
+        })?
+    }
+    let value = objects.remove(0);
+    {
+        let object = value.as_object().ok_or(HueError::ProtocolError {
+            msg: "expected first item to be an object".to_string(),
+        })?;
+        if let Some(e) = object.get("error").and_then(|o| o.as_object()) {
+            let code: u64 = e.get("type").and_then(|s| s.as_u64()).unwrap_or(0);
+            let field = |name: &str| {
+                e.get(name)
+                    .and_then(|s| s.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let code = HueErrorCode::from(code as usize);
+            let (address, msg) = (field("address"), field("description"));
+            return Err(if code.is_internal() {
+                HueError::BridgeInternalError { code, address, msg }
+            } else {
+                HueError::BridgeError { code, address, msg }
+            });
+        }
+    }
+    Ok(from_value(value)?)
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn body(command: &CommandLight) -> String {
+        serde_json::to_string(command).unwrap()
+    }
+
+    #[test]
+    fn each_color_mode_sends_only_its_fields() {
+        let hs = CommandLight::default().on().with_hue(46920).with_sat(254);
+        assert_eq!(body(&hs), r#"{"on":true,"hue":46920,"sat":254}"#);
+        let xy = CommandLight::default().on().with_xy(0.5, 0.25);
+        assert_eq!(body(&xy), r#"{"on":true,"xy":[0.5,0.25]}"#);
+        let ct = CommandLight::default().on().with_ct(366);
+        assert_eq!(body(&ct), r#"{"on":true,"ct":366}"#);
+    }
+
+    #[test]
+    fn only_set_fields_are_serialized() {
+        let keys = [
+            "on",
+            "bri",
+            "hue",
+            "sat",
+            "ct",
+            "xy",
+            "transitiontime",
+            "alert",
+            "effect",
+        ];
+        for subset in 0..1u32 << keys.len() {
+            let set = |i: usize| subset & (1 << i) != 0;
+            let command = CommandLight {
+                on: Some(true).filter(|_| set(0)),
+                bri: Some(100).filter(|_| set(1)),
+                hue: Some(1000).filter(|_| set(2)),
+                sat: Some(200).filter(|_| set(3)),
+                ct: Some(300).filter(|_| set(4)),
+                xy: Some((0.5, 0.5)).filter(|_| set(5)),
+                transitiontime: Some(4).filter(|_| set(6)),
+                alert: Some("select".to_string()).filter(|_| set(7)),
+                effect: Some("none".to_string()).filter(|_| set(8)),
+            };
+            let value = serde_json::to_value(&command).unwrap();
+            let object = value.as_object().unwrap();
+            let mut sent: Vec<_> = object.keys().map(String::as_str).collect();
+            let mut expected: Vec<_> = (0..keys.len())
+                .filter(|&i| set(i))
+                .map(|i| keys[i])
+                .collect();
+            sent.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(sent, expected);
+            assert!(!object.values().any(serde_json::Value::is_null));
+        }
+    }
+
+    /// A command with the fields of `subset` set, to values that depend on
+    /// `n`.
+    fn partial_command(subset: u32, n: u8) -> CommandLight {
+        let set = |i: usize| subset & (1 << i) != 0;
+        CommandLight {
+            on: Some(n > 1).filter(|_| set(0)),
+            bri: Some(n).filter(|_| set(1)),
+            hue: Some(n as u16 * 100).filter(|_| set(2)),
+            sat: Some(n).filter(|_| set(3)),
+            ct: Some(153 + n as u16).filter(|_| set(4)),
+            xy: Some((n as f32 / 8.0, 0.5)).filter(|_| set(5)),
+            transitiontime: Some(n as u16).filter(|_| set(6)),
+            alert: Some(format!("alert{}", n)).filter(|_| set(7)),
+            effect: Some(format!("effect{}", n)).filter(|_| set(8)),
+        }
+    }
+
+    #[test]
+    fn merged_fields_come_from_the_overlay_when_set() {
+        const ALL: u32 = (1 << 9) - 1;
+        let overlay_all = || serde_json::to_value(partial_command(ALL, 2)).unwrap();
+        for subset in 0..=ALL {
+            let base = partial_command(ALL, 1);
+            let overlay = partial_command(subset, 2);
+            let merged = serde_json::to_value(base.clone().merge(overlay.clone())).unwrap();
+            let base = serde_json::to_value(base).unwrap();
+            let overlay = serde_json::to_value(overlay).unwrap();
+            for (key, value) in merged.as_object().unwrap() {
+                let expected = overlay.get(key).unwrap_or(&base[key]);
+                assert_eq!(value, expected, "{} with overlay {:09b}", key, subset);
+            }
+
+            let or = partial_command(ALL, 1) | partial_command(subset, 2);
+            assert_eq!(serde_json::to_value(or).unwrap(), merged);
+            let under = partial_command(subset, 1) | partial_command(ALL, 2);
+            assert_eq!(serde_json::to_value(under).unwrap(), overlay_all());
+        }
+        let nothing = CommandLight::default() | CommandLight::default();
+        assert_eq!(body(&nothing), "{}");
+    }
+
+    #[test]
+    fn schedule_times_round_trip() {
+        let noon = NaiveTime::from_hms_opt(12, 5, 9).unwrap();
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let cases = [
+            (
+                ScheduleTime::once_at(&NaiveDateTime::new(date, noon)),
+                "2020-01-31T12:05:09",
+            ),
+            (ScheduleTime::daily_at(noon), "W127/T12:05:09"),
+            (
+                ScheduleTime::Weekly {
+                    weekdays: 0b0011111,
+                    time: noon,
+                },
+                "W031/T12:05:09",
+            ),
+            (ScheduleTime::after(Duration::from_secs(600)), "PT00:10:00"),
+            (
+                ScheduleTime::every(Duration::from_secs(3661)),
+                "R/PT01:01:01",
+            ),
+            (
+                ScheduleTime::every_n_times(Duration::from_secs(30), 5),
+                "R05/PT00:00:30",
+            ),
+        ];
+        for (time, localtime) in cases.iter() {
+            assert_eq!(time.to_localtime_string(), *localtime);
+            assert_eq!(
+                ScheduleTime::from_localtime_string(localtime).unwrap(),
+                *time
+            );
+            assert!(Schedule::validate_localtime(localtime));
+        }
+    }
+
+    #[test]
+    fn unsupported_schedule_times_are_rejected() {
+        // Valid for the bridge, but randomized times have no ScheduleTime.
+        assert!(Schedule::validate_localtime("W127/T07:30:00A00:05:00"));
+        for localtime in &[
+            "W127/T07:30:00A00:05:00",
+            "PT00:10:00A00:01:00",
+            "W128/T07:30:00",
+            "PT24:00:00",
+            "R00/PT00:10:00",
+            "2020-02-30T07:30:00",
+            "07:30",
+        ] {
+            match ScheduleTime::from_localtime_string(localtime) {
+                Err(HueError::InvalidArgument { .. }) => {}
+                other => panic!("{} parsed as {:?}", localtime, other),
             }
         }
-        Ok(from_value(value)?)
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn credentials_stay_out_of_logs() {
+        for path in &["/api", "/api/user", "/api/user/", "/api/user/config"] {
+            assert!(carries_credentials(path), "{}", path);
+        }
+        for path in &["/api/user/lights", "/api/user/groups/1/action"] {
+            assert!(!carries_credentials(path), "{}", path);
+        }
+        let url = |s| reqwest::Url::parse(s).unwrap();
+        assert_eq!(
+            redact_url(&url("http://10.0.0.2/api/secret/lights"), Some("secret")),
+            "http://10.0.0.2/api/<username>/lights"
+        );
+        assert_eq!(
+            redact_url(
+                &url("http://10.0.0.2/api/secret/config/whitelist/other"),
+                Some("secret")
+            ),
+            "http://10.0.0.2/api/<username>/config/whitelist/<username>"
+        );
+    }
+
+    #[test]
+    fn retry_delays_grow_linearly() {
+        let backoff = Duration::from_millis(200);
+        let delays: Vec<_> = (0..4).map(|a| retry_delay(backoff, a)).collect();
+        assert_eq!(delays, [200, 400, 600, 800].map(Duration::from_millis));
+        assert_eq!(retry_delay(backoff, 1000), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(Duration::MAX, 1), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn transitions_are_rounded_to_bridge_units() {
+        let units = |ms| {
+            CommandLight::default()
+                .with_transition(Duration::from_millis(ms))
+                .transitiontime
+        };
+        assert_eq!(units(0), Some(0));
+        assert_eq!(units(1), Some(1));
+        assert_eq!(units(49), Some(1));
+        assert_eq!(units(50), Some(1));
+        assert_eq!(units(149), Some(1));
+        assert_eq!(units(150), Some(2));
+        assert_eq!(units(u64::MAX), Some(u16::MAX));
     }
 }
@@ -0,0 +1,345 @@
+//! Conversions between RGB and the two color spaces understood by the
+//! bridge: hue/saturation/brightness and CIE 1931 xy chromaticity.
+//!
+//! The hue wheel spans `0..=65535`, while saturation and brightness top at
+//! 254, the highest value the bridge accepts.
+//!
+//! No gamut clipping happens in the conversions: [`rgb_to_xy`] may return a
+//! point that a given lamp cannot render. The bridge then moves it to the
+//! closest point of the lamp gamut, which can noticeably shift saturated
+//! greens and blues; [`ColorGamut::clamp_xy`] does the same ahead of time.
+
+const MAX_HUE: f64 = 65535.0;
+const MAX_SAT_BRI: f64 = 254.0;
+
+/// D65 white point, returned for black which has no chromaticity.
+const WHITE_POINT: (f32, f32) = (0.3127, 0.3290);
+
+pub fn rgb_to_hsb(r: u8, g: u8, b: u8) -> (u16, u8, u8) {
+    let r = r as f64 / 255f64;
+    let g = g as f64 / 255f64;
+    let b = b as f64 / 255f64;
+    let max = r.max(g.max(b));
+    let min = r.min(g.min(b));
+    let bri = (max * MAX_SAT_BRI).round() as u8;
+
+    if max == min {
+        return (0, 0, bri);
+    }
+    let d = max - min;
+    let s = d / max;
+    let h = if max == r {
+        (g - b) / d + (if g < b { 6f64 } else { 0f64 })
+    } else if max == g {
+        (b - r) / d + 2f64
+    } else {
+        (r - g) / d + 4f64
+    };
+    (
+        (h / 6f64 * MAX_HUE).round() as u16,
+        (s * MAX_SAT_BRI).round() as u8,
+        bri,
+    )
+}
+
+pub fn hsb_to_rgb(hue: u16, sat: u8, bri: u8) -> (u8, u8, u8) {
+    let h = hue as f64 / MAX_HUE * 6f64;
+    let s = (sat as f64 / MAX_SAT_BRI).min(1.0);
+    let v = (bri as f64 / MAX_SAT_BRI).min(1.0);
+    let sector = (h.floor() as u32) % 6;
+    let f = h - h.floor();
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    let (r, g, b) = match sector {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Converts sRGB to xy following the Philips recipe: gamma expansion, then
+/// the Wide RGB D65 matrix.
+pub fn rgb_to_xy(r: u8, g: u8, b: u8) -> (f32, f32) {
+    let r = gamma_expand(r as f64 / 255f64);
+    let g = gamma_expand(g as f64 / 255f64);
+    let b = gamma_expand(b as f64 / 255f64);
+    let x = r * 0.664_511 + g * 0.154_324 + b * 0.162_028;
+    let y = r * 0.283_881 + g * 0.668_433 + b * 0.047_685;
+    let z = r * 0.000_088 + g * 0.072_310 + b * 0.986_039;
+    let sum = x + y + z;
+    if sum == 0.0 {
+        return WHITE_POINT;
+    }
+    ((x / sum) as f32, (y / sum) as f32)
+}
+
+/// Inverse of [`rgb_to_xy`] at full luminance: the result is scaled so that
+/// its brightest channel is 255.
+pub fn xy_to_rgb(x: f32, y: f32) -> (u8, u8, u8) {
+    if y <= 0.0 {
+        return (0, 0, 0);
+    }
+    let (x, y) = (x as f64, y as f64);
+    let big_y = 1.0;
+    let big_x = big_y / y * x;
+    let big_z = big_y / y * (1.0 - x - y);
+    let r = big_x * 1.656_492 - big_y * 0.354_851 - big_z * 0.255_038;
+    let g = -big_x * 0.707_196 + big_y * 1.655_397 + big_z * 0.036_152;
+    let b = big_x * 0.051_713 - big_y * 0.121_364 + big_z * 1.011_530;
+    let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+    let max = r.max(g.max(b));
+    if max == 0.0 {
+        return (0, 0, 0);
+    }
+    (
+        to_u8(gamma_compress(r / max)),
+        to_u8(gamma_compress(g / max)),
+        to_u8(gamma_compress(b / max)),
+    )
+}
+
+/// The `ct` range accepted by the bridge, about 6500K down to 2000K.
+const MIN_MIREDS: u16 = 153;
+const MAX_MIREDS: u16 = 500;
+
+/// A color temperature in kelvin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Kelvin(pub u32);
+
+/// A color temperature in mireds, the unit of `ct`: a million over the
+/// temperature in kelvin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Mireds(pub u16);
+
+/// Clamps to the range the bridge accepts.
+impl From<Kelvin> for Mireds {
+    fn from(k: Kelvin) -> Mireds {
+        let mireds = (1_000_000.0 / k.0.max(1) as f64).round();
+        Mireds((mireds.min(MAX_MIREDS as f64) as u16).max(MIN_MIREDS))
+    }
+}
+
+/// Clamps to the range the bridge accepts.
+impl From<Mireds> for Kelvin {
+    fn from(m: Mireds) -> Kelvin {
+        let mireds = m.0.clamp(MIN_MIREDS, MAX_MIREDS);
+        Kelvin((1_000_000.0 / mireds as f64).round() as u32)
+    }
+}
+
+impl std::fmt::Display for Kelvin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}K", self.0)
+    }
+}
+
+impl std::fmt::Display for Mireds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} mireds", self.0)
+    }
+}
+
+/// Color gamuts of Philips lamps, each a triangle in xy space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ColorGamut {
+    A,
+    B,
+    C,
+    Custom {
+        red: (f32, f32),
+        green: (f32, f32),
+        blue: (f32, f32),
+    },
+}
+
+const GAMUT_A_MODELS: &[&str] = &[
+    "LLC001", "LLC005", "LLC006", "LLC007", "LLC010", "LLC011", "LLC012", "LLC013", "LLC014",
+    "LST001",
+];
+const GAMUT_B_MODELS: &[&str] = &["LCT001", "LCT002", "LCT003", "LCT007", "LLM001"];
+const GAMUT_C_MODELS: &[&str] = &[
+    "LCT010", "LCT011", "LCT012", "LCT014", "LCT015", "LCT016", "LLC020", "LST002", "LCA001",
+    "LCA002", "LCA003",
+];
+
+impl ColorGamut {
+    pub fn for_model(modelid: &str) -> Option<ColorGamut> {
+        if GAMUT_A_MODELS.contains(&modelid) {
+            Some(ColorGamut::A)
+        } else if GAMUT_B_MODELS.contains(&modelid) {
+            Some(ColorGamut::B)
+        } else if GAMUT_C_MODELS.contains(&modelid) {
+            Some(ColorGamut::C)
+        } else {
+            None
+        }
+    }
+
+    /// The red, green and blue corners.
+    pub fn vertices(&self) -> [(f32, f32); 3] {
+        match *self {
+            ColorGamut::A => [(0.704, 0.296), (0.2151, 0.7106), (0.138, 0.08)],
+            ColorGamut::B => [(0.675, 0.322), (0.409, 0.518), (0.167, 0.04)],
+            ColorGamut::C => [(0.692, 0.308), (0.17, 0.7), (0.153, 0.048)],
+            ColorGamut::Custom { red, green, blue } => [red, green, blue],
+        }
+    }
+
+    /// Returns points inside the gamut unchanged, and moves the others to
+    /// the closest point of the triangle edges.
+    pub fn clamp_xy(&self, x: f32, y: f32) -> (f32, f32) {
+        let [red, green, blue] = self.vertices();
+        if in_triangle((x, y), red, green, blue) {
+            return (x, y);
+        }
+        [(red, green), (green, blue), (blue, red)]
+            .iter()
+            .map(|&(a, b)| closest_on_segment((x, y), a, b))
+            .min_by(|p, q| {
+                distance2((x, y), *p)
+                    .partial_cmp(&distance2((x, y), *q))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or((x, y))
+    }
+}
+
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+fn in_triangle(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> bool {
+    let (d1, d2, d3) = (cross(a, b, p), cross(b, c, p), cross(c, a, p));
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_neg && has_pos)
+}
+
+fn closest_on_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    let ab = (b.0 - a.0, b.1 - a.1);
+    let len2 = ab.0 * ab.0 + ab.1 * ab.1;
+    if len2 == 0.0 {
+        return a;
+    }
+    let t = (((p.0 - a.0) * ab.0 + (p.1 - a.1) * ab.1) / len2).clamp(0.0, 1.0);
+    (a.0 + ab.0 * t, a.1 + ab.1 * t)
+}
+
+fn distance2(p: (f32, f32), q: (f32, f32)) -> f32 {
+    (p.0 - q.0).powi(2) + (p.1 - q.1).powi(2)
+}
+
+fn gamma_expand(v: f64) -> f64 {
+    if v > 0.04045 {
+        ((v + 0.055) / 1.055).powf(2.4)
+    } else {
+        v / 12.92
+    }
+}
+
+fn gamma_compress(v: f64) -> f64 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn to_u8(v: f64) -> u8 {
+    (v.clamp(0.0, 1.0) * 255f64).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn hsb_round_trips_through_rgb() {
+        for &rgb in &[
+            (255, 0, 0),
+            (0, 255, 0),
+            (0, 0, 255),
+            (255, 255, 255),
+            (255, 128, 0),
+            (12, 200, 180),
+            (90, 30, 120),
+            (0, 0, 0),
+        ] {
+            let (hue, sat, bri) = rgb_to_hsb(rgb.0, rgb.1, rgb.2);
+            let (r, g, b) = hsb_to_rgb(hue, sat, bri);
+            let off_by = |a: u8, b: u8| (a as i16 - b as i16).abs();
+            assert!(
+                off_by(r, rgb.0) <= 1 && off_by(g, rgb.1) <= 1 && off_by(b, rgb.2) <= 1,
+                "{:?} came back as {:?}",
+                rgb,
+                (r, g, b)
+            );
+        }
+        assert_eq!(rgb_to_hsb(255, 0, 0), (0, 254, 254));
+        assert_eq!(rgb_to_hsb(0, 0, 255), (43690, 254, 254));
+    }
+
+    #[test]
+    fn primaries_match_the_wide_rgb_d65_corners() {
+        assert_close(rgb_to_xy(255, 0, 0), (0.7006, 0.2993));
+        assert_close(rgb_to_xy(0, 255, 0), (0.1724, 0.7468));
+        assert_close(rgb_to_xy(0, 0, 255), (0.1355, 0.0399));
+        assert_close(rgb_to_xy(255, 255, 255), (0.3227, 0.3290));
+        assert_eq!(rgb_to_xy(0, 0, 0), WHITE_POINT);
+    }
+
+    #[test]
+    fn mireds_round_trip_at_both_ends_of_the_range() {
+        for &mireds in &[MIN_MIREDS, MAX_MIREDS] {
+            let kelvin = Kelvin::from(Mireds(mireds));
+            assert_eq!(Mireds::from(kelvin), Mireds(mireds));
+        }
+        assert_eq!(Kelvin::from(Mireds(500)), Kelvin(2000));
+    }
+
+    #[test]
+    fn temperatures_out_of_range_are_clamped() {
+        assert_eq!(Mireds::from(Kelvin(10_000)), Mireds(MIN_MIREDS));
+        assert_eq!(Mireds::from(Kelvin(1000)), Mireds(MAX_MIREDS));
+        assert_eq!(Mireds::from(Kelvin(0)), Mireds(MAX_MIREDS));
+        assert_eq!(Kelvin::from(Mireds(100)), Kelvin::from(Mireds(MIN_MIREDS)));
+        assert_eq!(Kelvin::from(Mireds(600)), Kelvin(2000));
+    }
+
+    #[test]
+    fn points_inside_the_gamut_are_kept() {
+        let gamut = ColorGamut::C;
+        assert_eq!(gamut.clamp_xy(0.3, 0.3), (0.3, 0.3));
+        for &corner in &gamut.vertices() {
+            assert_eq!(gamut.clamp_xy(corner.0, corner.1), corner);
+        }
+    }
+
+    #[test]
+    fn points_outside_the_gamut_move_to_the_closest_edge() {
+        let gamut = ColorGamut::C;
+        let [red, green, blue] = gamut.vertices();
+        assert_close(gamut.clamp_xy(0.9, 0.3), red);
+        assert_close(gamut.clamp_xy(0.1, 0.9), green);
+        assert_close(gamut.clamp_xy(0.1, 0.0), blue);
+
+        let (x, y) = gamut.clamp_xy(0.4, 0.0);
+        assert!(cross(blue, red, (x, y)).abs() < 1e-6);
+        assert!(blue.0 < x && x < red.0);
+    }
+}
@@ -1,28 +1,39 @@
-use crate::{HueError, HueError::DiscoveryError};
-use reqwest;
+use crate::HueError;
+#[cfg(feature = "serde")]
+use crate::HueError::DiscoveryError;
+#[cfg(feature = "serde")]
 use serde_json::{Map, Value};
 use std::net::IpAddr;
 
-pub fn discover_hue_bridge() -> Result<IpAddr, HueError> {
-    let n_upnp_result = discover_hue_bridge_n_upnp();
-    if n_upnp_result.is_err() {
-        discover_hue_bridge_upnp()
-    } else {
-        n_upnp_result
-    }
+#[cfg(feature = "async")]
+pub async fn discover_hue_bridges_n_upnp_async() -> Result<Vec<IpAddr>, HueError> {
+    let objects: Vec<Map<String, Value>> = reqwest::get("https://discovery.meethue.com/")
+        .await?
+        .json()
+        .await?;
+    objects.iter().map(parse_n_upnp_object).collect()
 }
 
-pub fn discover_hue_bridge_n_upnp() -> Result<IpAddr, HueError> {
+pub fn discover_hue_bridges_upnp() -> Result<Vec<IpAddr>, HueError> {
+    // use 'IpBridge' as a marker and a max duration of 5s as per
+    // https://developers.meethue.com/develop/application-design-guidance/hue-bridge-discovery/
+    Ok(
+        ssdp_probe::ssdp_probe_v4(br"IpBridge", 64, std::time::Duration::from_secs(5))?
+            .into_iter()
+            .map(IpAddr::from)
+            .collect(),
+    )
+}
+
+#[cfg(feature = "serde")]
+pub fn discover_hue_bridges_n_upnp() -> Result<Vec<IpAddr>, HueError> {
     let objects: Vec<Map<String, Value>> =
         reqwest::blocking::get("https://discovery.meethue.com/")?.json()?;
+    objects.iter().map(parse_n_upnp_object).collect()
+}
 
-    if objects.len() == 0 {
-        Err(DiscoveryError {
-            msg: "expected non-empty array".into(),
-        })?
-    }
-    let ref object = objects[0];
-
+#[cfg(feature = "serde")]
+fn parse_n_upnp_object(object: &Map<String, Value>) -> Result<IpAddr, HueError> {
     let ip = object.get("internalipaddress").ok_or(DiscoveryError {
         msg: "Expected internalipaddress".into(),
     })?;
@@ -33,16 +44,3 @@ pub fn discover_hue_bridge_n_upnp() -> Result<IpAddr, HueError> {
         })?
         .parse()?)
 }
-
-pub fn discover_hue_bridge_upnp() -> Result<IpAddr, HueError> {
-    // use 'IpBridge' as a marker and a max duration of 5s as per
-    // https://developers.meethue.com/develop/application-design-guidance/hue-bridge-discovery/
-    Ok(
-        ssdp_probe::ssdp_probe_v4(br"IpBridge", 1, std::time::Duration::from_secs(5))?
-            .first()
-            .map(|it| it.to_owned().into())
-            .ok_or(DiscoveryError {
-                msg: "could not find bridge".into(),
-            })?,
-    )
-}
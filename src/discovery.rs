@@ -0,0 +1,58 @@
+//! Discovery of every bridge on the network, for networks with several
+//! bridges or where one of the two discovery methods is blocked.
+
+use std::net::IpAddr;
+
+use crate::disco;
+use crate::HueError;
+
+/// Asks the bridges with an SSDP probe of five seconds, then falls back to
+/// the `discovery.meethue.com` N-UPnP service when no bridge answers, e.g.
+/// because multicast is filtered. Addresses are unique and sorted. The
+/// fallback needs the `serde` feature, as the service answers JSON.
+pub fn discover_bridges() -> Vec<String> {
+    discover_bridge_ips()
+        .into_iter()
+        .map(|ip| ip.to_string())
+        .collect()
+}
+
+pub(crate) fn discover_bridge_ips() -> Vec<IpAddr> {
+    let ips = disco::discover_hue_bridges_upnp().unwrap_or_default();
+    #[cfg(feature = "serde")]
+    let ips = if ips.is_empty() {
+        disco::discover_hue_bridges_n_upnp().unwrap_or_default()
+    } else {
+        ips
+    };
+    sorted_unique(ips)
+}
+
+/// `discover_bridge_ips` for `BridgeAsync`: the SSDP probe still blocks,
+/// the N-UPnP fallback does not.
+#[cfg(feature = "async")]
+pub(crate) async fn discover_bridge_ips_async() -> Vec<IpAddr> {
+    let mut ips = disco::discover_hue_bridges_upnp().unwrap_or_default();
+    if ips.is_empty() {
+        ips = disco::discover_hue_bridges_n_upnp_async()
+            .await
+            .unwrap_or_default();
+    }
+    sorted_unique(ips)
+}
+
+fn sorted_unique(mut ips: Vec<IpAddr>) -> Vec<IpAddr> {
+    ips.sort_by_key(|ip| ip.to_string());
+    ips.dedup();
+    ips
+}
+
+/// The first of `discover_bridge_ips`.
+pub(crate) fn discover_bridge_ip() -> Result<IpAddr, HueError> {
+    discover_bridge_ips()
+        .into_iter()
+        .next()
+        .ok_or_else(|| HueError::DiscoveryError {
+            msg: "could not find bridge".into(),
+        })
+}
@@ -1,24 +1,333 @@
+#![allow(clippy::result_large_err)]
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum HueError {
     #[error("An error occurred while performing an HTTP request")]
-    Reqwest(#[from] reqwest::Error),
+    Reqwest(#[source] reqwest::Error),
+    /// The bridge could not be reached, or the connection broke while
+    /// exchanging the request.
+    #[error("A network error occurred while talking to the bridge")]
+    NetworkError(#[source] reqwest::Error),
+    #[error("The bridge did not answer in time")]
+    Timeout,
+    #[cfg(feature = "serde")]
     #[error("An error occurred while manipulating JSON")]
     SerdeJson(#[from] serde_json::Error),
+    #[error("An error occurred while reading or writing a file")]
+    Io(#[from] std::io::Error),
     #[error("An error occurred while parsing an address")]
     AddrParse(#[from] std::net::AddrParseError),
+    #[cfg(feature = "tls")]
+    #[error("An error occurred while setting up TLS")]
+    Tls(#[from] native_tls::Error),
     #[error("An error occurred during SSDP discovery")]
     SSDP(#[from] ssdp_probe::SsdpProbeError),
     #[error("A protocol error occurred: {}", msg)]
     ProtocolError { msg: String },
-    #[error("The bridge reported error code {}: {}", code, msg)]
-    BridgeError { code: usize, msg: String },
+    #[error("Hue bridge error {} at {}: {}", code, address, msg)]
+    BridgeError {
+        code: HueErrorCode,
+        address: String,
+        msg: String,
+    },
+    /// An error of the 9xx range: the bridge failed on its side rather than
+    /// rejected the request. If it persists, the bridge needs a reset.
+    #[error("Hue bridge internal error {} at {}: {}", code, address, msg)]
+    BridgeInternalError {
+        code: HueErrorCode,
+        address: String,
+        msg: String,
+    },
     #[error("A discovery error occurred: {}", msg)]
     DiscoveryError { msg: String },
+    #[error("An invalid argument was given: {}", msg)]
+    InvalidArgument { msg: String },
     #[error("This action requires an username to be registered")]
     NoUsername,
 }
 
+impl From<reqwest::Error> for HueError {
+    fn from(e: reqwest::Error) -> HueError {
+        if e.is_timeout() {
+            HueError::Timeout
+        } else if e.is_connect() || e.is_request() || e.is_body() {
+            HueError::NetworkError(e)
+        } else {
+            HueError::Reqwest(e)
+        }
+    }
+}
+
+impl HueError {
+    /// The message of the error and of its sources, which is what equality
+    /// and hashing look at: the wrapped errors cannot be compared.
+    fn chain(&self) -> String {
+        let mut chain = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            chain.push_str(": ");
+            chain.push_str(&e.to_string());
+            source = e.source();
+        }
+        chain
+    }
+}
+
+/// Errors of the same kind with the same messages are equal, so bridge
+/// errors repeated for several resources can be deduplicated.
+impl PartialEq for HueError {
+    fn eq(&self, other: &HueError) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && self.chain() == other.chain()
+    }
+}
+
+impl Eq for HueError {}
+
+impl std::hash::Hash for HueError {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        self.chain().hash(state);
+    }
+}
+
+/// The error `type` numbers documented for the v1 API.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HueErrorCode {
+    UnauthorizedUser,
+    InvalidJson,
+    ResourceNotAvailable,
+    MethodNotAvailable,
+    MissingParameters,
+    ParameterNotAvailable,
+    InvalidValue,
+    ParameterNotModifiable,
+    TooManyItemsInList,
+    PortalConnectionRequired,
+    LinkButtonNotPressed,
+    DhcpCannotBeDisabled,
+    InvalidUpdateState,
+    DeviceIsOff,
+    GroupTableFull,
+    DeviceGroupTableFull,
+    DeviceUnreachable,
+    GroupNotModifiable,
+    LightAlreadyUsed,
+    SceneBufferFull,
+    SceneLocked,
+    SensorTypeNotAllowed,
+    SensorListFull,
+    RuleEngineFull,
+    ConditionError,
+    ActionError,
+    UnableToActivate,
+    ScheduleListFull,
+    ScheduleTimezoneNotValid,
+    ScheduleTimeConflict,
+    CannotCreateSchedule,
+    ScheduleTimeInPast,
+    CommandError,
+    InternalError,
+    Unknown(usize),
+}
+
+impl HueErrorCode {
+    pub fn code(&self) -> usize {
+        match *self {
+            HueErrorCode::UnauthorizedUser => 1,
+            HueErrorCode::InvalidJson => 2,
+            HueErrorCode::ResourceNotAvailable => 3,
+            HueErrorCode::MethodNotAvailable => 4,
+            HueErrorCode::MissingParameters => 5,
+            HueErrorCode::ParameterNotAvailable => 6,
+            HueErrorCode::InvalidValue => 7,
+            HueErrorCode::ParameterNotModifiable => 8,
+            HueErrorCode::TooManyItemsInList => 11,
+            HueErrorCode::PortalConnectionRequired => 12,
+            HueErrorCode::LinkButtonNotPressed => 101,
+            HueErrorCode::DhcpCannotBeDisabled => 110,
+            HueErrorCode::InvalidUpdateState => 111,
+            HueErrorCode::DeviceIsOff => 201,
+            HueErrorCode::GroupTableFull => 301,
+            HueErrorCode::DeviceGroupTableFull => 302,
+            HueErrorCode::DeviceUnreachable => 304,
+            HueErrorCode::GroupNotModifiable => 305,
+            HueErrorCode::LightAlreadyUsed => 306,
+            HueErrorCode::SceneBufferFull => 402,
+            HueErrorCode::SceneLocked => 403,
+            HueErrorCode::SensorTypeNotAllowed => 501,
+            HueErrorCode::SensorListFull => 502,
+            HueErrorCode::RuleEngineFull => 601,
+            HueErrorCode::ConditionError => 607,
+            HueErrorCode::ActionError => 608,
+            HueErrorCode::UnableToActivate => 609,
+            HueErrorCode::ScheduleListFull => 701,
+            HueErrorCode::ScheduleTimezoneNotValid => 702,
+            HueErrorCode::ScheduleTimeConflict => 703,
+            HueErrorCode::CannotCreateSchedule => 704,
+            HueErrorCode::ScheduleTimeInPast => 705,
+            HueErrorCode::CommandError => 706,
+            HueErrorCode::InternalError => 901,
+            HueErrorCode::Unknown(code) => code,
+        }
+    }
+
+    /// Codes 900 to 999, reported as `HueError::BridgeInternalError`.
+    pub fn is_internal(&self) -> bool {
+        (900..1000).contains(&self.code())
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            HueErrorCode::UnauthorizedUser => "unauthorized user",
+            HueErrorCode::InvalidJson => "body contains invalid JSON",
+            HueErrorCode::ResourceNotAvailable => "resource not available",
+            HueErrorCode::MethodNotAvailable => "method not available for resource",
+            HueErrorCode::MissingParameters => "missing parameters in body",
+            HueErrorCode::ParameterNotAvailable => "parameter not available",
+            HueErrorCode::InvalidValue => "invalid value for parameter",
+            HueErrorCode::ParameterNotModifiable => "parameter is not modifiable",
+            HueErrorCode::TooManyItemsInList => "too many items in list",
+            HueErrorCode::PortalConnectionRequired => "portal connection required",
+            HueErrorCode::LinkButtonNotPressed => "link button not pressed",
+            HueErrorCode::DhcpCannotBeDisabled => "DHCP cannot be disabled",
+            HueErrorCode::InvalidUpdateState => "invalid updatestate",
+            HueErrorCode::DeviceIsOff => "parameter not modifiable, device is set to off",
+            HueErrorCode::GroupTableFull => "group could not be created, group table full",
+            HueErrorCode::DeviceGroupTableFull => {
+                "device could not be added to group, device group table full"
+            }
+            HueErrorCode::DeviceUnreachable => "device is unreachable",
+            HueErrorCode::GroupNotModifiable => "group of this type cannot be updated or deleted",
+            HueErrorCode::LightAlreadyUsed => "light is already used in another room",
+            HueErrorCode::SceneBufferFull => "scene could not be created, scene buffer full",
+            HueErrorCode::SceneLocked => "scene could not be removed, it is locked",
+            HueErrorCode::SensorTypeNotAllowed => "sensor type cannot be created",
+            HueErrorCode::SensorListFull => "sensor list is full",
+            HueErrorCode::RuleEngineFull => "rule engine full",
+            HueErrorCode::ConditionError => "condition error",
+            HueErrorCode::ActionError => "action error",
+            HueErrorCode::UnableToActivate => "unable to activate",
+            HueErrorCode::ScheduleListFull => "schedule list is full",
+            HueErrorCode::ScheduleTimezoneNotValid => "schedule time-zone not valid",
+            HueErrorCode::ScheduleTimeConflict => "schedule cannot set time and local time",
+            HueErrorCode::CannotCreateSchedule => "cannot create schedule",
+            HueErrorCode::ScheduleTimeInPast => "cannot enable schedule, time is in the past",
+            HueErrorCode::CommandError => "command error",
+            HueErrorCode::InternalError => "internal error",
+            HueErrorCode::Unknown(_) => "unknown error",
+        }
+    }
+}
+
+impl From<usize> for HueErrorCode {
+    fn from(code: usize) -> HueErrorCode {
+        match code {
+            1 => HueErrorCode::UnauthorizedUser,
+            2 => HueErrorCode::InvalidJson,
+            3 => HueErrorCode::ResourceNotAvailable,
+            4 => HueErrorCode::MethodNotAvailable,
+            5 => HueErrorCode::MissingParameters,
+            6 => HueErrorCode::ParameterNotAvailable,
+            7 => HueErrorCode::InvalidValue,
+            8 => HueErrorCode::ParameterNotModifiable,
+            11 => HueErrorCode::TooManyItemsInList,
+            12 => HueErrorCode::PortalConnectionRequired,
+            101 => HueErrorCode::LinkButtonNotPressed,
+            110 => HueErrorCode::DhcpCannotBeDisabled,
+            111 => HueErrorCode::InvalidUpdateState,
+            201 => HueErrorCode::DeviceIsOff,
+            301 => HueErrorCode::GroupTableFull,
+            302 => HueErrorCode::DeviceGroupTableFull,
+            304 => HueErrorCode::DeviceUnreachable,
+            305 => HueErrorCode::GroupNotModifiable,
+            306 => HueErrorCode::LightAlreadyUsed,
+            402 => HueErrorCode::SceneBufferFull,
+            403 => HueErrorCode::SceneLocked,
+            501 => HueErrorCode::SensorTypeNotAllowed,
+            502 => HueErrorCode::SensorListFull,
+            601 => HueErrorCode::RuleEngineFull,
+            607 => HueErrorCode::ConditionError,
+            608 => HueErrorCode::ActionError,
+            609 => HueErrorCode::UnableToActivate,
+            701 => HueErrorCode::ScheduleListFull,
+            702 => HueErrorCode::ScheduleTimezoneNotValid,
+            703 => HueErrorCode::ScheduleTimeConflict,
+            704 => HueErrorCode::CannotCreateSchedule,
+            705 => HueErrorCode::ScheduleTimeInPast,
+            706 => HueErrorCode::CommandError,
+            901 => HueErrorCode::InternalError,
+            code => HueErrorCode::Unknown(code),
+        }
+    }
+}
+
+impl std::fmt::Display for HueErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[cfg(feature = "serde")]
+pub mod api;
+#[cfg(feature = "async")]
+pub mod async_bridge;
 pub mod bridge;
+pub mod color;
 mod disco;
+pub mod discovery;
+#[cfg(feature = "serde")]
+pub mod entertainment;
+#[cfg(feature = "serde")]
+pub mod poller;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "serde")]
+pub mod transition;
+pub mod util;
+#[cfg(all(feature = "tls", feature = "serde"))]
+pub mod v2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    fn device_is_off() -> Result<(), Box<dyn Error + Send + Sync>> {
+        Err(HueError::BridgeError {
+            code: HueErrorCode::from(201),
+            address: "/lights/2/state/bri".to_string(),
+            msg: "parameter, bri, is not modifiable. Device is set to off.".to_string(),
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn bridge_errors_name_code_and_address() {
+        let error = device_is_off().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Hue bridge error 201 at /lights/2/state/bri: \
+             parameter, bri, is not modifiable. Device is set to off."
+        );
+        match error.downcast_ref::<HueError>() {
+            Some(HueError::BridgeError { code, .. }) => {
+                assert_eq!(*code, HueErrorCode::DeviceIsOff)
+            }
+            other => panic!("expected a bridge error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wrapped_errors_are_kept_as_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no backup there");
+        let error: Box<dyn Error + Send + Sync + 'static> = Box::new(HueError::from(io));
+        let source = error.source().expect("the io error is lost");
+        assert_eq!(source.to_string(), "no backup there");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+    }
+}
@@ -0,0 +1,82 @@
+//! Change notifications for v1 bridges, which can only be polled.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::bridge::{Bridge, IdentifiedLight, LightState};
+use crate::util::diff_light_states;
+use crate::HueError;
+
+type LightCallback = Box<dyn Fn(&IdentifiedLight, &LightState) + Send>;
+
+pub struct Poller {
+    bridge: Arc<Bridge>,
+    interval: Duration,
+    // Handed to the polling thread by `start`.
+    light_callbacks: Option<Vec<LightCallback>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Poller {
+    pub fn new(bridge: Arc<Bridge>, interval: Duration) -> Poller {
+        Poller {
+            bridge,
+            interval,
+            light_callbacks: Some(Vec::new()),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Called with the light as it is now and its previous state, for every
+    /// light whose state differs from the last poll. Lights that appear or
+    /// disappear between two polls are not reported.
+    pub fn on_light_change<F: Fn(&IdentifiedLight, &LightState) + Send + 'static>(
+        mut self,
+        cb: F,
+    ) -> Self {
+        if let Some(callbacks) = &mut self.light_callbacks {
+            callbacks.push(Box::new(cb));
+        }
+        self
+    }
+
+    /// Polls on a background thread until `stop` is called. Failed polls are
+    /// skipped, and the next one compares against the last good snapshot.
+    /// A poller starts once: the callbacks belong to its thread.
+    pub fn start(&mut self) -> Result<JoinHandle<()>, HueError> {
+        let callbacks = self
+            .light_callbacks
+            .take()
+            .ok_or_else(|| HueError::InvalidArgument {
+                msg: "the poller is already started".into(),
+            })?;
+        let bridge = self.bridge.clone();
+        let interval = self.interval;
+        let stop = self.stop.clone();
+        Ok(std::thread::spawn(move || {
+            let mut previous: Option<Vec<IdentifiedLight>> = None;
+            while !stop.load(Ordering::Relaxed) {
+                if let Ok(lights) = bridge.get_all_lights() {
+                    if let Some(previous) = &previous {
+                        for diff in diff_light_states(previous, &lights) {
+                            if let (Some(state), Some(light)) =
+                                (diff.before, lights.iter().find(|l| l.id == diff.id))
+                            {
+                                callbacks.iter().for_each(|cb| cb(light, &state))
+                            }
+                        }
+                    }
+                    previous = Some(lights);
+                }
+                std::thread::sleep(interval);
+            }
+        }))
+    }
+
+    /// Takes effect at the end of the current interval.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
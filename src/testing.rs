@@ -0,0 +1,72 @@
+//! A [`HueBridgeApi`](crate::api::HueBridgeApi) implementation answering
+//! from canned results, for testing code built on this crate without a
+//! bridge. Enabled by the `testing` feature.
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    pub method: &'static str,
+    /// The `Debug` rendering of each argument.
+    pub args: Vec<String>,
+}
+
+/// Results are queued per method name and handed out in order. A call with
+/// nothing queued fails with `HueError::ProtocolError`.
+#[derive(Default)]
+pub struct MockBridge {
+    results: Mutex<HashMap<&'static str, VecDeque<Box<dyn Any + Send>>>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockBridge {
+    pub fn new() -> MockBridge {
+        MockBridge::default()
+    }
+
+    /// Queues the answer to the next call of `method`, e.g.
+    /// `mock.push_result("get_light", Ok(light))`. `T` must be the return
+    /// type of the method, or the call panics.
+    pub fn push_result<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        result: Result<T, HueError>,
+    ) {
+        self.results
+            .lock()
+            .unwrap()
+            .entry(method)
+            .or_default()
+            .push_back(Box::new(result));
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub(crate) fn call<T: 'static>(
+        &self,
+        method: &'static str,
+        args: Vec<String>,
+    ) -> Result<T, HueError> {
+        self.calls.lock().unwrap().push(MockCall { method, args });
+        let result = self
+            .results
+            .lock()
+            .unwrap()
+            .get_mut(method)
+            .and_then(|queue| queue.pop_front());
+        match result {
+            Some(result) => *result
+                .downcast::<Result<T, HueError>>()
+                .unwrap_or_else(|_| panic!("result queued for {} has the wrong type", method)),
+            None => Err(HueError::ProtocolError {
+                msg: format!("no result queued for {}", method),
+            }),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Timed sequences of light commands, for effects such as a sunrise.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::bridge::{Bridge, CommandLight};
+use crate::HueError;
+
+pub struct Transition {
+    bridge: Arc<Bridge>,
+    light_id: usize,
+    steps: Vec<(Duration, CommandLight)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Transition {
+    pub fn new(bridge: Arc<Bridge>, light_id: usize) -> Transition {
+        Transition {
+            bridge,
+            light_id,
+            steps: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Sends `cmd` once `after` has elapsed since the previous step, or
+    /// since the start for the first one. Give `cmd` a transition time to
+    /// fade between steps rather than jump.
+    pub fn add_step(&mut self, after: Duration, cmd: CommandLight) -> &mut Self {
+        self.steps.push((after, cmd));
+        self
+    }
+
+    /// Plays the steps once on a background thread. Steps the bridge
+    /// rejects are skipped.
+    pub fn play(&self) -> JoinHandle<()> {
+        self.spawn(false)
+    }
+
+    /// Plays the steps over and over until `stop` is called. At least one
+    /// step has to wait, otherwise the loop would flood the bridge.
+    pub fn play_loop(&self) -> Result<JoinHandle<()>, HueError> {
+        if self.steps.iter().all(|(after, _)| after.is_zero()) {
+            return Err(HueError::InvalidArgument {
+                msg: "a looping transition needs a step with a delay".into(),
+            });
+        }
+        Ok(self.spawn(true))
+    }
+
+    /// Takes effect before the next step.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn spawn(&self, repeat: bool) -> JoinHandle<()> {
+        let bridge = self.bridge.clone();
+        let light_id = self.light_id;
+        let steps = self.steps.clone();
+        let stop = self.stop.clone();
+        stop.store(false, Ordering::Relaxed);
+        std::thread::spawn(move || loop {
+            for (after, cmd) in &steps {
+                std::thread::sleep(*after);
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let _ = bridge.set_light_state(light_id, cmd);
+            }
+            if !repeat {
+                return;
+            }
+        })
+    }
+}
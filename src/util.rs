@@ -0,0 +1,117 @@
+//! Helpers built on top of the bridge API.
+
+use std::collections::BTreeMap;
+
+use crate::bridge::{IdentifiedLight, LightState};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightDiff {
+    pub id: usize,
+    /// `None` for a light that appeared.
+    pub before: Option<LightState>,
+    /// `None` for a light that disappeared.
+    pub after: Option<LightState>,
+    /// The `LightState` fields that differ; empty when the light appeared
+    /// or disappeared.
+    pub changed_fields: Vec<&'static str>,
+}
+
+/// Lists the lights whose state changed, appeared or disappeared between two
+/// snapshots, sorted by id.
+pub fn diff_light_states(before: &[IdentifiedLight], after: &[IdentifiedLight]) -> Vec<LightDiff> {
+    let mut states: BTreeMap<usize, (Option<LightState>, Option<LightState>)> = BTreeMap::new();
+    for light in before {
+        states.entry(light.id).or_default().0 = Some(light.light.state);
+    }
+    for light in after {
+        states.entry(light.id).or_default().1 = Some(light.light.state);
+    }
+    states
+        .into_iter()
+        .filter_map(|(id, (before, after))| {
+            let changed_fields = match (&before, &after) {
+                (Some(b), Some(a)) => changed_fields(b, a),
+                _ => Vec::new(),
+            };
+            if before.is_some() && after.is_some() && changed_fields.is_empty() {
+                return None;
+            }
+            Some(LightDiff {
+                id,
+                before,
+                after,
+                changed_fields,
+            })
+        })
+        .collect()
+}
+
+fn changed_fields(b: &LightState, a: &LightState) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    macro_rules! compare {
+        ($($field:ident),*) => {
+            $(if b.$field != a.$field {
+                fields.push(stringify!($field));
+            })*
+        };
+    }
+    compare!(on, bri, hue, sat, ct, xy, effect, alert, colormode, reachable);
+    fields
+}
+
+/// Dimmest first, see [`IdentifiedLight::cmp_by_brightness`].
+pub fn sort_lights_by_brightness(lights: &mut [IdentifiedLight]) {
+    lights.sort_by(|a, b| a.cmp_by_brightness(b));
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::bridge::Light;
+
+    fn lights() -> Vec<IdentifiedLight> {
+        let lights: BTreeMap<String, Light> =
+            serde_json::from_str(include_str!("../tests/fixtures/lights.json")).unwrap();
+        lights
+            .into_iter()
+            .map(|(id, light)| IdentifiedLight {
+                id: id.parse().unwrap(),
+                light,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_lights_are_left_out() {
+        assert!(diff_light_states(&lights(), &lights()).is_empty());
+    }
+
+    #[test]
+    fn changed_fields_are_named() {
+        let mut after = lights();
+        after[0].light.state.on = false;
+        after[0].light.state.bri = Some(10);
+        let diff = diff_light_states(&lights(), &after);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].id, 1);
+        assert_eq!(diff[0].changed_fields, ["on", "bri"]);
+        assert_eq!(diff[0].before, Some(lights()[0].light.state));
+        assert_eq!(diff[0].after, Some(after[0].light.state));
+    }
+
+    #[test]
+    fn added_and_removed_lights_are_reported() {
+        let before = lights();
+        let after = vec![before[1].clone()];
+        let diff = diff_light_states(&before, &after);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].id, 1);
+        assert_eq!(diff[0].after, None);
+        assert!(diff[0].changed_fields.is_empty());
+
+        let diff = diff_light_states(&after, &before);
+        assert_eq!(diff[0].id, 1);
+        assert_eq!(diff[0].before, None);
+        assert_eq!(diff[0].after, Some(before[0].light.state));
+    }
+}
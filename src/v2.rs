@@ -0,0 +1,212 @@
+//! Beginnings of the CLIP v2 API, served under `/clip/v2` over HTTPS only.
+//! Requests authenticate with a `hue-application-key` header, the v1
+//! username, and answers come wrapped in `data`/`errors` envelopes.
+//! Enabled by the `tls` feature.
+
+use std::io::{BufRead, BufReader, Lines};
+use std::net::IpAddr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::bridge::pinned_tls;
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnState {
+    pub on: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DimmingState {
+    /// Percentage, from `min_dim_level` to 100.
+    pub brightness: f64,
+    pub min_dim_level: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct XyPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorState {
+    pub xy: XyPoint,
+    pub gamut_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorTemperatureState {
+    /// `None` while the light is in another color mode.
+    pub mirek: Option<u16>,
+    pub mirek_valid: bool,
+}
+
+/// Lights only report the features they have: a plug has no `dimming`, a
+/// white lamp no `color`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightV2 {
+    pub id: String,
+    pub id_v1: Option<String>,
+    pub on: OnState,
+    pub dimming: Option<DimmingState>,
+    pub color: Option<ColorState>,
+    pub color_temperature: Option<ColorTemperatureState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceIdentifier {
+    pub rid: String,
+    pub rtype: String,
+}
+
+/// One change pushed on the event stream; `data` holds the changed parts of
+/// the resources, each with its `id` and `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HueEvent {
+    pub creationtime: String,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    #[serde(default)]
+    errors: Vec<EnvelopeError>,
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct EnvelopeError {
+    description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BridgeV2 {
+    pub ip: IpAddr,
+    pub app_key: String,
+    client: reqwest::blocking::Client,
+    tls: native_tls::TlsConnector,
+}
+
+impl BridgeV2 {
+    /// `cert_pem` is the bridge certificate, see
+    /// [`Bridge::with_tls`](crate::bridge::Bridge::with_tls).
+    pub fn new(ip: IpAddr, app_key: String, cert_pem: &[u8]) -> Result<BridgeV2, HueError> {
+        let tls = pinned_tls(cert_pem)?;
+        let client = reqwest::blocking::Client::builder()
+            .use_preconfigured_tls(tls.clone())
+            .build()?;
+        Ok(BridgeV2 {
+            ip,
+            app_key,
+            client,
+            tls,
+        })
+    }
+
+    /// Opens the server-sent event stream. Each item gathers the events of
+    /// one message; the iterator ends when the bridge closes the stream.
+    pub fn subscribe_events(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<Vec<HueEvent>, HueError>>, HueError> {
+        // The stream stays open for as long as we read it, so it must not
+        // inherit the default request timeout.
+        let client = reqwest::blocking::Client::builder()
+            .use_preconfigured_tls(self.tls.clone())
+            .timeout(None)
+            .build()?;
+        let response = client
+            .get(&format!("https://{}/eventstream/clip/v2", self.ip)[..])
+            .header("hue-application-key", &self.app_key[..])
+            .header("Accept", "text/event-stream")
+            .send()?
+            .error_for_status()?;
+        Ok(EventStream {
+            lines: BufReader::new(response).lines(),
+        })
+    }
+
+    pub fn get_lights(&self) -> Result<Vec<LightV2>, HueError> {
+        self.send_v2_get("resource/light")
+    }
+
+    pub fn set_light_on(&self, id: &str, on: bool) -> Result<(), HueError> {
+        self.send_v2_put(
+            &format!("resource/light/{}", id),
+            &LightOn { on: OnState { on } },
+        )?;
+        Ok(())
+    }
+
+    fn send_v2_get<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>, HueError> {
+        self.send_v2(self.client.get(&self.url(path)[..]))
+    }
+
+    fn send_v2_put(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<Vec<ResourceIdentifier>, HueError> {
+        self.send_v2(self.client.put(&self.url(path)[..]).json(body))
+    }
+
+    fn send_v2<T: DeserializeOwned>(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<Vec<T>, HueError> {
+        let envelope: Envelope<T> = request
+            .header("hue-application-key", &self.app_key[..])
+            .send()?
+            .json()?;
+        if !envelope.errors.is_empty() {
+            let msg = envelope
+                .errors
+                .into_iter()
+                .map(|e| e.description)
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(HueError::ProtocolError { msg });
+        }
+        Ok(envelope.data)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("https://{}/clip/v2/{}", self.ip, path)
+    }
+}
+
+#[derive(Serialize)]
+struct LightOn {
+    on: OnState,
+}
+
+struct EventStream {
+    lines: Lines<BufReader<reqwest::blocking::Response>>,
+}
+
+impl Iterator for EventStream {
+    type Item = Result<Vec<HueEvent>, HueError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut data = String::new();
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.is_empty() {
+                if !data.is_empty() {
+                    return Some(serde_json::from_str(&data).map_err(HueError::from));
+                }
+            } else if let Some(chunk) = line.strip_prefix("data:") {
+                data.push_str(chunk.trim_start());
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Runs the blocking client against a fake bridge: a local HTTP server that
+//! answers every request with a canned body and records what it was sent.
+
+#![cfg(feature = "serde")]
+#![allow(clippy::result_large_err)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use hueclient::bridge::{Bridge, CommandLight};
+use hueclient::poller::Poller;
+use hueclient::transition::Transition;
+use hueclient::{HueError, HueErrorCode};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone)]
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+impl Request {
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+struct FakeBridge {
+    port: u16,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl FakeBridge {
+    /// Answers every request with `body`; the server lives until the test
+    /// process exits.
+    fn serve(body: &'static str) -> FakeBridge {
+        FakeBridge::serve_with_status("200 OK", body)
+    }
+
+    fn serve_with_status(status: &'static str, body: &'static str) -> FakeBridge {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let request = read_request(&mut BufReader::new(&stream));
+                recorded.lock().unwrap().push(request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        FakeBridge { port, requests }
+    }
+
+    fn bridge(&self) -> Bridge {
+        Bridge::for_ip(IpAddr::from([127, 0, 0, 1])).with_port(self.port)
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Request {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    Request {
+        method,
+        path,
+        body: String::from_utf8(body).unwrap(),
+    }
+}
+
+#[test]
+fn get_all_lights_parses_the_light_list() {
+    let fake = FakeBridge::serve(include_str!("fixtures/lights.json"));
+    let lights = fake
+        .bridge()
+        .with_user("user".to_string())
+        .get_all_lights()
+        .unwrap();
+
+    assert_eq!(lights.len(), 2);
+    assert_eq!(lights[0].id, 1);
+    assert_eq!(lights[0].light.name, "Living room");
+    assert_eq!(lights[0].light.light_type, "Extended color light");
+    assert!(lights[0].light.state.on);
+    assert_eq!(lights[0].light.state.bri, Some(200));
+    assert_eq!(lights[0].light.state.ct, Some(366));
+    assert_eq!(lights[1].id, 2);
+    assert_eq!(lights[1].light.state.hue, None);
+    assert_eq!(lights[1].light.state.reachable, Some(false));
+
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].path, "/api/user/lights");
+}
+
+#[test]
+fn set_light_state_sends_only_the_given_fields() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    let command = CommandLight::default().on().with_bri(100);
+    fake.bridge()
+        .with_user("user".to_string())
+        .set_light_state(1, &command)
+        .unwrap();
+
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].path, "/api/user/lights/1/state");
+    assert_eq!(requests[0].json(), json!({ "on": true, "bri": 100 }));
+}
+
+#[test]
+fn register_user_reports_the_link_button() {
+    let fake = FakeBridge::serve(include_str!("fixtures/link_button_not_pressed.json"));
+    let mut bridge = fake.bridge();
+    match bridge.register_user("hueclient#test") {
+        Err(HueError::BridgeError { code, .. }) => {
+            assert_eq!(code, HueErrorCode::LinkButtonNotPressed)
+        }
+        other => panic!("expected a link button error, got {:?}", other),
+    }
+    assert_eq!(bridge.username, None);
+
+    let requests = fake.requests();
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/api");
+    assert_eq!(
+        requests[0].json(),
+        json!({ "devicetype": "hueclient#test" })
+    );
+}
+
+#[test]
+fn bridge_errors_of_write_answers_are_returned() {
+    let fake = FakeBridge::serve(include_str!("fixtures/device_is_off.json"));
+    let command = CommandLight::default().with_bri(100);
+    match fake
+        .bridge()
+        .with_user("user".to_string())
+        .set_light_state(2, &command)
+    {
+        Err(HueError::BridgeError { code, address, msg }) => {
+            assert_eq!(code, HueErrorCode::DeviceIsOff);
+            assert_eq!(address, "/lights/2/state/bri");
+            assert!(msg.contains("Device is set to off"), "{}", msg);
+        }
+        other => panic!("expected a bridge error, got {:?}", other),
+    }
+}
+
+#[test]
+fn reachable_lights_are_filtered_locally() {
+    let fake = FakeBridge::serve(include_str!("fixtures/lights.json"));
+    let bridge = fake.bridge().with_user("user".to_string());
+    let reachable = bridge.get_reachable_lights().unwrap();
+    assert_eq!(reachable.iter().map(|l| l.id).collect::<Vec<_>>(), [1]);
+    let dimmable = bridge.get_lights_of_type("Dimmable light").unwrap();
+    assert_eq!(dimmable.iter().map(|l| l.id).collect::<Vec<_>>(), [2]);
+}
+
+#[test]
+fn set_all_lights_uses_group_zero() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    fake.bridge()
+        .with_user("user".to_string())
+        .dim_all_lights(10)
+        .unwrap();
+
+    let requests = fake.requests();
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].path, "/api/user/groups/0/action");
+    assert_eq!(requests[0].json(), json!({ "bri": 10 }));
+}
+
+#[test]
+fn timezone_list_is_a_plain_array() {
+    let fake = FakeBridge::serve(include_str!("fixtures/timezones.json"));
+    let timezones = fake
+        .bridge()
+        .with_user("user".to_string())
+        .get_timezone_list()
+        .unwrap();
+    assert_eq!(timezones.len(), 5);
+    assert!(timezones.iter().any(|tz| tz == "Europe/Paris"));
+    assert_eq!(fake.requests()[0].path, "/api/user/info/timezones");
+}
+
+#[test]
+fn internal_errors_are_set_apart() {
+    let fake = FakeBridge::serve(include_str!("fixtures/internal_error.json"));
+    match fake
+        .bridge()
+        .with_user("user".to_string())
+        .dim_all_lights(10)
+    {
+        Err(HueError::BridgeInternalError { code, .. }) => {
+            assert_eq!(code, HueErrorCode::InternalError)
+        }
+        other => panic!("expected an internal error, got {:?}", other),
+    }
+}
+
+#[test]
+fn scenes_are_filtered_by_group() {
+    let fake = FakeBridge::serve(include_str!("fixtures/scenes.json"));
+    let scenes = fake
+        .bridge()
+        .with_user("user".to_string())
+        .get_scenes_for_group(1)
+        .unwrap();
+    assert_eq!(scenes.len(), 1);
+    assert_eq!(scenes[0].id, "4e1c6b20e-on-0");
+    assert_eq!(scenes[0].name, "Relax");
+    assert!(!scenes[0].locked);
+}
+
+#[test]
+fn batch_commands_keep_their_order() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    let commands: Vec<_> = (1..=6)
+        .map(|id| (id, CommandLight::default().with_bri(id as u8)))
+        .collect();
+    let results = fake
+        .bridge()
+        .with_user("user".to_string())
+        .with_max_concurrency(3)
+        .batch_set_light_states(&commands)
+        .unwrap();
+
+    assert_eq!(
+        results.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5, 6]
+    );
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+    let mut paths: Vec<_> = fake.requests().into_iter().map(|r| r.path).collect();
+    paths.sort();
+    assert_eq!(paths[0], "/api/user/lights/1/state");
+    assert_eq!(paths.len(), 6);
+}
+
+#[test]
+fn created_scenes_can_be_recalled() {
+    let fake = FakeBridge::serve(include_str!("fixtures/scene_created.json"));
+    let bridge = fake.bridge().with_user("user".to_string());
+    let id = bridge.create_scene("Evening", &[1, 2]).unwrap();
+    assert_eq!(id, "Ab3rPm7kQ2lz9Xo");
+    bridge.activate_scene(1, &id).unwrap();
+
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/api/user/scenes");
+    assert_eq!(
+        requests[0].json(),
+        json!({ "name": "Evening", "lights": ["1", "2"], "recycle": false })
+    );
+    assert_eq!(requests[1].method, "PUT");
+    assert_eq!(requests[1].path, "/api/user/groups/1/action");
+    assert_eq!(requests[1].json(), json!({ "scene": "Ab3rPm7kQ2lz9Xo" }));
+}
+
+#[test]
+fn one_bridge_can_be_shared_between_threads() {
+    let fake = FakeBridge::serve(include_str!("fixtures/lights.json"));
+    let bridge = fake.bridge().with_user("user".to_string());
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let bridge = bridge.clone();
+            thread::spawn(move || bridge.get_all_lights().unwrap())
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap().len(), 2);
+    }
+    assert_eq!(fake.requests().len(), 4);
+}
+
+#[test]
+fn long_names_are_rejected_before_sending() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    let bridge = fake.bridge().with_user("user".to_string());
+    let name = "x".repeat(33);
+    match bridge.set_light_name(1, &name) {
+        Err(HueError::InvalidArgument { .. }) => {}
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    match bridge.set_group_name(1, &name) {
+        Err(HueError::InvalidArgument { .. }) => {}
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    assert!(fake.requests().is_empty());
+
+    bridge.set_light_name(1, &name[..32]).unwrap();
+    assert_eq!(fake.requests()[0].json(), json!({ "name": &name[..32] }));
+}
+
+#[test]
+fn pollers_start_once() {
+    let fake = FakeBridge::serve(include_str!("fixtures/lights.json"));
+    let bridge = Arc::new(fake.bridge().with_user("user".to_string()));
+    let mut poller = Poller::new(bridge, Duration::from_millis(1)).on_light_change(|_, _| {});
+    let handle = poller.start().unwrap();
+    match poller.start() {
+        Err(HueError::InvalidArgument { .. }) => {}
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    poller.stop();
+    handle.join().unwrap();
+}
+
+#[test]
+fn loops_without_a_delay_are_rejected() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    let bridge = Arc::new(fake.bridge().with_user("user".to_string()));
+    let mut transition = Transition::new(bridge, 1);
+    transition
+        .add_step(Duration::from_secs(0), CommandLight::default().on())
+        .add_step(Duration::from_secs(0), CommandLight::default().off());
+    match transition.play_loop() {
+        Err(HueError::InvalidArgument { .. }) => {}
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    assert!(fake.requests().is_empty());
+}
+
+#[test]
+fn invalid_batch_commands_create_no_group() {
+    let fake = FakeBridge::serve(include_str!("fixtures/set_light_state.json"));
+    let bridge = fake.bridge().with_user("user".to_string());
+    let command = CommandLight::default().with_ct(300).with_hue(1000);
+    match bridge.set_multiple_lights(&[1, 2], command) {
+        Err(HueError::InvalidArgument { .. }) => {}
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    assert!(fake.requests().is_empty());
+}
+
+#[test]
+fn only_idempotent_requests_are_retried() {
+    let fake = FakeBridge::serve_with_status("503 Service Unavailable", "[]");
+    let bridge = fake
+        .bridge()
+        .with_user("user".to_string())
+        .with_retry(2, Duration::from_millis(1));
+    let _ = bridge.get_all_lights();
+    assert_eq!(fake.requests().len(), 3);
+    assert!(fake.requests().iter().all(|r| r.method == "GET"));
+
+    let _ = bridge.create_scene("Evening", &[1]);
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[3].method, "POST");
+}
@@ -0,0 +1,67 @@
+//! Deserializes the answers in tests/fixtures into the crate types, then
+//! checks that serializing them again gives JSON that reads back the same.
+
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use hueclient::bridge::{BridgeConfig, Group, Light, Rule, Scene, Schedule, Sensor};
+
+fn roundtrip<T: Serialize + DeserializeOwned>(fixture: &str) -> Value {
+    let parsed: T = serde_json::from_str(fixture).expect("fixture does not deserialize");
+    let serialized = serde_json::to_value(&parsed).unwrap();
+    let reparsed: T =
+        serde_json::from_value(serialized.clone()).expect("output does not read back");
+    assert_eq!(serde_json::to_value(&reparsed).unwrap(), serialized);
+    serialized
+}
+
+macro_rules! roundtrip_tests {
+    ($($name:ident: $ty:ty => $fixture:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                roundtrip::<$ty>(include_str!(concat!("fixtures/", $fixture)));
+            }
+        )*
+    };
+}
+
+roundtrip_tests! {
+    lights: BTreeMap<String, Light> => "lights.json",
+    groups: BTreeMap<String, Group> => "groups.json",
+    scenes: BTreeMap<String, Scene> => "scenes.json",
+    sensors: BTreeMap<String, Sensor> => "sensors.json",
+    schedules: BTreeMap<String, Schedule> => "schedules.json",
+    rules: BTreeMap<String, Rule> => "rules.json",
+    config: BridgeConfig => "config.json",
+}
+
+#[test]
+fn unset_fields_are_left_out() {
+    let lights = roundtrip::<BTreeMap<String, Light>>(include_str!("fixtures/lights.json"));
+    let state = lights["2"]["state"].as_object().unwrap();
+    assert!(!state.contains_key("hue"));
+    assert!(!state.values().any(Value::is_null));
+}
+
+#[test]
+fn lights_read_back_equal() {
+    let lights: BTreeMap<String, Light> =
+        serde_json::from_str(include_str!("fixtures/lights.json")).unwrap();
+    let serialized = serde_json::to_string(&lights).unwrap();
+    let reparsed: BTreeMap<String, Light> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(reparsed, lights);
+
+    let hallway = serde_json::to_value(&lights["2"]).unwrap();
+    assert_eq!(hallway["type"], "Dimmable light");
+    assert!(hallway.get("light_type").is_none());
+    assert_eq!(
+        hallway["state"],
+        serde_json::json!({ "on": false, "bri": 1, "alert": "none", "reachable": false })
+    );
+}